@@ -1,4 +1,4 @@
-use crate::{player, Game};
+use crate::{game::Game, player};
 use axum::{
     extract::{Extension, Json, Request},
     http::StatusCode,
@@ -7,7 +7,7 @@ use axum::{
 };
 use axum_extra::TypedHeader;
 use bcrypt::{hash, verify, DEFAULT_COST};
-use chrono::{TimeZone, Utc};
+use chrono::Utc;
 use edgedb_tokio::Queryable;
 use headers::{authorization::Bearer, Authorization};
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation, TokenData};
@@ -22,6 +22,10 @@ pub struct Player {
     password: String,
 }
 
+pub async fn validate_token(Extension(username): Extension<String>) -> impl IntoResponse {
+    (StatusCode::OK, Json(json!({"username": username})))
+}
+
 pub async fn login(
     Extension(game): Extension<Arc<RwLock<Game>>>,
     Json(payload): Json<Player>,
@@ -91,17 +95,17 @@ pub async fn login(
             player::Player::new(payload.username.clone(), token.clone()),
         );
 
-        return (
+        (
             StatusCode::ACCEPTED,
             Json(json!({"token": token, "admin": false})),
         )
-        .into_response();
+        .into_response()
     } else {
-        return (
+        (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": "Server error"})),
         )
-        .into_response();
+        .into_response()
     }
 }
 
@@ -155,7 +159,7 @@ pub async fn authenticate(
         .await
         .players
         .get(username)
-        .map_or(false, |player| {
+        .is_some_and(|player| {
             player.token == token && expiry > Utc::now().timestamp() as usize
         })
 }
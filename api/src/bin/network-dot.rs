@@ -0,0 +1,21 @@
+use std::{env, path::Path, process::ExitCode};
+
+use api::graph::Graph;
+
+// Print network data as a Graphviz graph, e.g. `cargo run --bin network-dot -- network.csv > network.dot`.
+// Prints the built in network when no file is given.
+fn main() -> ExitCode {
+    let graph = match env::args().nth(1) {
+        Some(path) => match Graph::load(Path::new(&path)) {
+            Ok(graph) => graph,
+            Err(error) => {
+                eprintln!("Could not load {}: {}", path, error);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Graph::from_csv(),
+    };
+
+    print!("{}", graph.to_dot());
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,36 @@
+use std::{env, path::Path, process::ExitCode};
+
+use api::graph::Graph;
+
+// Check network data without starting the server, e.g. `cargo run --bin validate-network -- network.csv`.
+// Takes the same csv or ron paths as the NETWORK_PATH setting.
+// Checks the built in network when no file is given. Exits with an error if anything is wrong.
+fn main() -> ExitCode {
+    let graph = match env::args().nth(1) {
+        Some(path) => match Graph::load(Path::new(&path)) {
+            Ok(graph) => graph,
+            Err(error) => {
+                eprintln!("Could not load {}: {}", path, error);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Graph::from_csv(),
+    };
+
+    let issues = graph.validate();
+    for issue in issues.iter() {
+        println!("{}", issue);
+    }
+    println!(
+        "{} stations, {} connections, {} problems",
+        graph.stations.len(),
+        graph.connections.len(),
+        issues.len()
+    );
+
+    if issues.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -24,6 +24,13 @@ impl Db {
         Db(db)
     }
 
+    // A client for a local database that is never connected to, so games can be made in tests.
+    #[cfg(test)]
+    pub async fn offline() -> Db {
+        let config = Builder::new().host("localhost").unwrap().build_env().await.unwrap();
+        Db(Client::new(&config))
+    }
+
     // Initializes a list of players from the database, username key.
     pub async fn init(&self) -> HashMap<String, Player> {
         const GET_USERS: &str = "select Player {username}";
@@ -0,0 +1,344 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{extract::ws::Message, response::IntoResponse, Extension, Json};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::{
+    game::Game,
+    graph::Graph,
+    station::{Code, Connection, WALK},
+};
+
+// Parts of the network that are closed for the current game, for suspended lines or house rules
+// like no Airport Express. Routing, zones and the nearest station all go around them.
+#[derive(Debug, Clone, Default)]
+pub struct Mask {
+    pub lines: HashSet<Code>,
+    pub stations: HashSet<Code>,
+    pub connections: HashSet<Connection>, // compared by their stations and line, the minutes don't matter
+}
+
+impl Mask {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.stations.is_empty() && self.connections.is_empty()
+    }
+
+    // Whether a connection can't be used, because it is closed itself, its line is or a station at either end is.
+    pub fn closes(&self, connection: &Connection) -> bool {
+        self.lines.contains(&connection.3)
+            || self.stations.contains(&connection.0)
+            || self.stations.contains(&connection.1)
+            || self.connections.contains(connection)
+    }
+
+    // Everything closed, sorted so it reads the same every time.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut lines: Vec<&Code> = self.lines.iter().collect();
+        lines.sort();
+        let mut stations: Vec<&Code> = self.stations.iter().collect();
+        stations.sort();
+        let mut connections: Vec<_> = self
+            .connections
+            .iter()
+            .map(|Connection(from, to, _, line)| {
+                if from <= to {
+                    (line, from, to)
+                } else {
+                    (line, to, from)
+                }
+            })
+            .collect();
+        connections.sort();
+        let connections: Vec<_> = connections
+            .into_iter()
+            .map(|(line, from, to)| json!({"from": from, "to": to, "line": line}))
+            .collect();
+
+        json!({
+            "lines": lines,
+            "stations": stations,
+            "connections": connections,
+        })
+    }
+}
+
+impl Graph {
+    // Close everything in a change, as long as all of it is part of the network.
+    pub fn close(&mut self, change: Change) -> Result<(), String> {
+        let connections = change.check(self)?;
+        self.mask.lines.extend(change.lines);
+        self.mask.stations.extend(change.stations);
+        self.mask.connections.extend(connections);
+        Ok(())
+    }
+
+    // Open up again everything in a change, the rest of what is closed stays closed.
+    pub fn open(&mut self, change: Change) -> Result<(), String> {
+        let connections = change.check(self)?;
+        for line in change.lines.iter() {
+            self.mask.lines.remove(line);
+        }
+        for code in change.stations.iter() {
+            self.mask.stations.remove(code);
+        }
+        for connection in connections.iter() {
+            self.mask.connections.remove(connection);
+        }
+        Ok(())
+    }
+
+    // Whether a station can be used, it isn't closed and at least one of its lines is still running.
+    pub fn is_open(&self, code: &Code) -> bool {
+        let Some(station) = self.stations.get(code) else {
+            return false;
+        };
+        !self.mask.stations.contains(code)
+            && station
+                .lines
+                .iter()
+                .any(|platform| !self.mask.lines.contains(&platform.line_code))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ConnectionChange {
+    from: Code,
+    to: Code,
+    line: Code,
+}
+
+// Lines, stations and connections to close or open again, any of them can be left out.
+#[derive(Deserialize)]
+pub struct Change {
+    #[serde(default)]
+    lines: Vec<Code>,
+    #[serde(default)]
+    stations: Vec<Code>,
+    #[serde(default)]
+    connections: Vec<ConnectionChange>,
+}
+
+impl Change {
+    // The connections of the change, checking everything in it is part of the network.
+    fn check(&self, graph: &Graph) -> Result<Vec<Connection>, String> {
+        // walking isn't one of the lines, but can be closed like one
+        if let Some(line) = self.lines.iter().find(|line| !graph.lines.contains_key(*line) && **line != WALK) {
+            return Err(format!("Line {} not found", line));
+        }
+        if let Some(code) = self
+            .stations
+            .iter()
+            .find(|code| !graph.stations.contains_key(*code))
+        {
+            return Err(format!("Station {} not found", code));
+        }
+        self.connections
+            .iter()
+            .map(|change| {
+                let connection = Connection(
+                    change.from.clone(),
+                    change.to.clone(),
+                    0,
+                    change.line.clone(),
+                );
+                match graph
+                    .connections
+                    .get(&connection)
+                    .or_else(|| graph.walks.get(&connection))
+                {
+                    Some(connection) => Ok(connection.clone()),
+                    None => Err(format!(
+                        "No connection from {} to {} on {}",
+                        change.from, change.to, change.line
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+// Let every player know what is closed, so maps and routes can be redrawn.
+async fn announce(game: &Game) {
+    let msg = json!({
+        "op": "network",
+        "closed": game.graph.mask.to_json(),
+    });
+    if let Err(e) = game.broadcast(Message::Text(msg.to_string())).await {
+        tracing::error!("Failed to send the network changes: {}", e);
+    }
+}
+
+// what is closed right now
+pub async fn get(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    Json(game.read().await.graph.mask.to_json())
+}
+
+// admin only, close lines, stations or connections for the current game
+// e.g. {"lines": ["AEL"], "stations": ["ADM"], "connections": [{"from": "TSW", "to": "TWH", "line": "TWL"}]}
+pub async fn close(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Extension(username): Extension<String>,
+    Json(change): Json<Change>,
+) -> impl IntoResponse {
+    let mut game = game.write().await;
+    if let Err(error) = game.graph.close(change) {
+        return Json(json!({"error": error}));
+    }
+    tracing::info!(
+        "admin {} closed part of the network, now closed: {}",
+        username,
+        game.graph.mask.to_json()
+    );
+
+    announce(&game).await;
+    Json(json!({"message": "Network updated", "closed": game.graph.mask.to_json()}))
+}
+
+// admin only, open up again what was closed, takes the same as `close`
+pub async fn open(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Extension(username): Extension<String>,
+    Json(change): Json<Change>,
+) -> impl IntoResponse {
+    let mut game = game.write().await;
+    if let Err(error) = game.graph.open(change) {
+        return Json(json!({"error": error}));
+    }
+    tracing::info!(
+        "admin {} opened part of the network, now closed: {}",
+        username,
+        game.graph.mask.to_json()
+    );
+
+    announce(&game).await;
+    Json(json!({"message": "Network updated", "closed": game.graph.mask.to_json()}))
+}
+
+// admin only, open the whole network again
+pub async fn clear(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Extension(username): Extension<String>,
+) -> impl IntoResponse {
+    tracing::info!("admin {} opened the whole network", username);
+    reopen(&game).await;
+    Json(json!({"message": "Network updated", "closed": game.read().await.graph.mask.to_json()}))
+}
+
+// Open everything up again, when an admin asks or at the end of a game.
+pub async fn reopen(game: &Arc<RwLock<Game>>) {
+    let mut game = game.write().await;
+    if game.graph.mask.is_empty() {
+        return;
+    }
+    game.graph.mask = Mask::default();
+    announce(&game).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn code(code: &str) -> Code {
+        Code::from_str(code).unwrap()
+    }
+
+    fn change(json: serde_json::Value) -> Change {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn lines(graph: &Graph, from: &str, to: &str) -> Vec<Code> {
+        graph.shortest_path(&code(from), &code(to)).unwrap().lines
+    }
+
+    fn reaches(graph: &Graph, from: &str, to: &str) -> bool {
+        graph
+            .reachable_within(&code(from), 120)
+            .iter()
+            .any(|(station, _)| station.code == code(to))
+    }
+
+    #[test]
+    fn closed_lines_are_avoided_until_opened_again() {
+        let mut graph = Graph::from_csv();
+        assert!(lines(&graph, "MOK", "CEN").contains(&code("TWL")));
+
+        graph.close(change(json!({"lines": ["TWL"]}))).unwrap();
+        assert!(!lines(&graph, "MOK", "CEN").contains(&code("TWL")));
+        // Tsuen Wan is only on the closed line
+        assert!(!graph.is_open(&code("TSW")));
+        assert!(graph.is_open(&code("MOK")));
+        assert!(!reaches(&graph, "MOK", "TSW"));
+        assert!(graph.shortest_path(&code("MOK"), &code("TSW")).is_none());
+
+        graph.open(change(json!({"lines": ["TWL"]}))).unwrap();
+        assert!(graph.mask.is_empty());
+        assert!(lines(&graph, "MOK", "CEN").contains(&code("TWL")));
+        assert!(reaches(&graph, "MOK", "TSW"));
+    }
+
+    #[test]
+    fn closed_stations_are_gone_around() {
+        let mut graph = Graph::from_csv();
+        let through = |graph: &Graph| graph.shortest_path(&code("PRE"), &code("YMT")).unwrap().stations;
+        assert!(through(&graph).contains(&code("MOK")));
+
+        graph.close(change(json!({"stations": ["MOK"]}))).unwrap();
+        assert!(!graph.is_open(&code("MOK")));
+        assert!(!through(&graph).contains(&code("MOK")));
+        assert!(!reaches(&graph, "PRE", "MOK"));
+        assert!(graph.shortest_path(&code("PRE"), &code("MOK")).is_none());
+
+        graph.open(change(json!({"stations": ["MOK"]}))).unwrap();
+        assert!(through(&graph).contains(&code("MOK")));
+        assert!(reaches(&graph, "PRE", "MOK"));
+    }
+
+    #[test]
+    fn closed_connections_are_not_used_either_way() {
+        let mut graph = Graph::from_csv();
+        // the only way out of Tsuen Wan by train
+        let closure = json!({"connections": [{"from": "TWH", "to": "TSW", "line": "TWL"}]});
+        graph.close(change(closure.clone())).unwrap();
+        assert!(graph.is_open(&code("TSW")));
+        assert!(graph.shortest_path(&code("TSW"), &code("TWH")).is_none());
+        assert!(graph.shortest_path(&code("TWH"), &code("TSW")).is_none());
+        assert!(!reaches(&graph, "TWH", "TSW"));
+
+        graph.open(change(closure)).unwrap();
+        assert_eq!(lines(&graph, "TSW", "TWH"), vec![code("TWL")]);
+        assert!(reaches(&graph, "TWH", "TSW"));
+    }
+
+    #[test]
+    fn only_parts_of_the_network_can_be_closed() {
+        let mut graph = Graph::from_csv();
+        assert_eq!(
+            graph.close(change(json!({"lines": ["XXX"]}))),
+            Err("Line XXX not found".to_string())
+        );
+        assert_eq!(
+            graph.close(change(json!({"stations": ["TWL", "QQQ"]}))),
+            Err("Station TWL not found".to_string())
+        );
+        assert_eq!(
+            graph.close(change(json!({"lines": ["AEL"], "connections": [{"from": "TSW", "to": "CEN", "line": "TWL"}]}))),
+            Err("No connection from TSW to CEN on TWL".to_string())
+        );
+        // nothing from a change that was refused is closed
+        assert!(graph.mask.is_empty());
+
+        // walking isn't a line, but can be closed as one
+        graph.close(change(json!({"lines": ["WLK"], "stations": ["ADM"]}))).unwrap();
+        assert!(graph.mask.closes(&Connection(code("CEN"), code("HOK"), 0, Code::walk())));
+        assert!(graph.mask.closes(&Connection(code("CEN"), code("ADM"), 0, code("ISL"))));
+        assert!(!graph.mask.closes(&Connection(code("CEN"), code("SHW"), 0, code("ISL"))));
+        assert_eq!(
+            graph.mask.to_json(),
+            json!({"lines": ["WLK"], "stations": ["ADM"], "connections": []})
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::{fmt::Write, sync::Arc};
+
+use axum::{http::header, response::IntoResponse, Extension, Json};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use crate::{
+    game::Game,
+    graph::Graph,
+    station::{Code, Station},
+};
+
+impl Graph {
+    // Stations as points and connections as lines, to check the data in any GIS tool.
+    pub fn to_geojson(&self) -> Value {
+        let mut stations: Vec<&Station> = self.stations.values().collect();
+        stations.sort_by(|a, b| a.code.cmp(&b.code));
+        let mut features: Vec<Value> = stations
+            .into_iter()
+            .map(|station| {
+                json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Point",
+                        "coordinates": [station.longitude, station.latitude],
+                    },
+                    "properties": {
+                        "code": station.code,
+                        "name": station.name,
+                        "lines": station.line_codes(),
+                    },
+                })
+            })
+            .collect();
+
+        // generated walks go last, marked so they can be styled or filtered out
+        let mut connections: Vec<_> = self.connections.iter().map(|connection| (connection, false)).collect();
+        connections.sort_by(|(a, _), (b, _)| (&a.3, &a.0, &a.1).cmp(&(&b.3, &b.0, &b.1)));
+        let mut walks: Vec<_> = self.walks.iter().map(|connection| (connection, true)).collect();
+        walks.sort_by(|(a, _), (b, _)| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        connections.extend(walks);
+        for (connection, walk) in connections {
+            let from = self.position(&connection.0, &connection.3);
+            let to = self.position(&connection.1, &connection.3);
+            let (Some(from), Some(to)) = (from, to) else {
+                continue;
+            };
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": [from, to],
+                },
+                "properties": {
+                    "from": connection.0,
+                    "to": connection.1,
+                    "line": connection.3,
+                    "minutes": connection.2,
+                    "walk": walk,
+                },
+            }));
+        }
+
+        json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+    }
+
+    // Where a line stops at a station as [longitude, latitude], the platform if there is one.
+    fn position(&self, code: &Code, line_code: &Code) -> Option<[f64; 2]> {
+        let station = self.stations.get(code)?;
+        let platform = station.lines.iter().find(|platform| platform.line_code == *line_code);
+        Some(match platform {
+            Some(platform) => [platform.longitude, platform.latitude],
+            None => [station.longitude, station.latitude],
+        })
+    }
+
+    // The network as a Graphviz graph, laid out like the map, e.g. `neato -n -Tsvg network.dot`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph network {\n    node [shape=circle, fontsize=10];\n");
+
+        let mut stations: Vec<&Station> = self.stations.values().collect();
+        stations.sort_by(|a, b| a.code.cmp(&b.code));
+        for station in stations {
+            let (x, y) = self.projection.project(station.latitude, station.longitude);
+            // graphviz has y going up, the map has it going down
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\\n{}\", pos=\"{},{}!\"];",
+                escape(&station.code.0),
+                escape(&station.code.0),
+                escape(&station.name),
+                x,
+                -y
+            );
+        }
+
+        let mut connections: Vec<_> = self.connections.iter().collect();
+        connections.sort_by(|a, b| (&a.3, &a.0, &a.1).cmp(&(&b.3, &b.0, &b.1)));
+        for connection in connections {
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -- \"{}\" [label=\"{} {}\"];",
+                escape(&connection.0 .0),
+                escape(&connection.1 .0),
+                escape(&connection.3 .0),
+                connection.2
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// Text for inside a quoted graphviz string, codes can have quotes in them as well as names.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// the loaded network as geojson
+pub async fn geojson(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    let geojson = game.read().await.graph.to_geojson();
+    ([(header::CONTENT_TYPE, "application/geo+json")], Json(geojson))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::settings::Settings;
+
+    // C is a few steps from A but on another line, so there is a walk between them
+    const NETWORK: &str = "A,Alpha,22.30,114.10,-,ONE
+B,Bravo,22.31,114.10,4,ONE
+,,,,,
+C,Charlie,22.3001,114.1001,-,TWO
+D,Delta,22.30,114.12,3,TWO";
+
+    #[test]
+    fn geojson_has_the_stations_then_the_connections_then_the_walks() {
+        let mut graph = Graph::parse_csv(NETWORK).unwrap();
+        graph.add_walks(&Settings::default());
+        let geojson = graph.to_geojson();
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 4 + 2 + 1);
+
+        assert_eq!(features[0]["geometry"], json!({"type": "Point", "coordinates": [114.10, 22.30]}));
+        assert_eq!(features[0]["properties"], json!({"code": "A", "name": "Alpha", "lines": ["ONE"]}));
+
+        assert_eq!(
+            features[4]["geometry"]["coordinates"],
+            json!([[114.10, 22.30], [114.10, 22.31]])
+        );
+        assert_eq!(
+            features[4]["properties"],
+            json!({"from": "A", "to": "B", "line": "ONE", "minutes": 4, "walk": false})
+        );
+        assert_eq!(features[5]["properties"]["line"], "TWO");
+        assert_eq!(features[6]["properties"]["line"], "WLK");
+        assert_eq!(features[6]["properties"]["walk"], true);
+    }
+
+    #[test]
+    fn dot_has_a_node_for_each_station_and_an_edge_for_each_connection() {
+        let dot = Graph::parse_csv(NETWORK).unwrap().to_dot();
+        assert!(dot.starts_with("graph network {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert_eq!(dot.lines().filter(|line| line.contains(" [label=\"") && line.contains("pos=")).count(), 4);
+        assert!(dot.contains("    \"A\" -- \"B\" [label=\"ONE 4\"];\n"));
+        assert!(dot.contains("    \"C\" -- \"D\" [label=\"TWO 3\"];\n"));
+        // walks are left out, they would cover the map in edges
+        assert!(!dot.contains("WLK"));
+    }
+
+    #[test]
+    fn dot_escapes_quotes_in_codes_and_names() {
+        let dot = Graph::parse_csv(
+            "A\"1,The \"Alpha\" \\ Omega,22.30,114.10,-,ONE
+B,Bravo,22.31,114.10,4,ONE",
+        )
+        .unwrap()
+        .to_dot();
+        assert!(dot.contains("    \"A\\\"1\" [label=\"A\\\"1\\nThe \\\"Alpha\\\" \\\\ Omega\", pos="));
+        assert!(dot.contains("    \"A\\\"1\" -- \"B\" [label=\"ONE 4\"];\n"));
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    str::FromStr,
+    sync::Arc,
+};
+
+use axum::{extract::Query, response::IntoResponse, Extension, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::{
+    game::Game,
+    graph::{Graph, Route},
+    station::{Code, WALK},
+};
+
+// What journeys cost: a fixed fare to get on a train, so much for every minute on one and extra for premium lines.
+// A ride between two stations in the table costs what the table says instead, like the published fares.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fares {
+    pub currency: String,
+    pub base: f64,       // once for any journey by train, walking is free
+    pub per_minute: f64, // for every minute on a train
+    #[serde(default)]
+    pub premiums: HashMap<Code, f64>, // extra every time a line is boarded, like the Airport Express
+    #[serde(default)]
+    pub table: Vec<(Code, Code, f64)>, // fixed fares for a ride between two stations, either way round
+}
+
+// roughly adult Octopus fares on the MTR
+impl Default for Fares {
+    fn default() -> Self {
+        let code = |code: &str| Code::from_str(code).unwrap();
+        let table = [
+            ("HOK", "AIR", 115.0),
+            ("KOW", "AIR", 105.0),
+            ("TSY", "AIR", 70.0),
+            ("HOK", "AWE", 115.0),
+            ("KOW", "AWE", 105.0),
+            ("TSY", "AWE", 70.0),
+        ]
+        .into_iter()
+        .map(|(from, to, fare)| (code(from), code(to), fare))
+        .collect();
+
+        Fares {
+            currency: "HKD".to_string(),
+            base: 4.0,
+            per_minute: 0.45,
+            premiums: HashMap::from([(code("AEL"), 20.0)]),
+            table,
+        }
+    }
+}
+
+// money is added up in cents, so fares compare exactly
+fn cents(amount: f64) -> u64 {
+    (amount.max(0.0) * 100.0).round() as u64
+}
+
+impl Fares {
+    // A fixed fare between two stations, if there is one.
+    fn fixed(&self, from: &Code, to: &Code) -> Option<f64> {
+        self.table
+            .iter()
+            .find(|(a, b, _)| a == from && b == to || a == to && b == from)
+            .map(|(_, _, fare)| *fare)
+    }
+}
+
+// The cheapest way between two stations and what it costs.
+#[derive(Debug, Clone, Serialize)]
+pub struct Fare {
+    pub fare: f64,
+    pub currency: String,
+    pub route: Route,
+}
+
+// A station, the line it was arrived on and whether a train has been taken yet, so the base fare is only paid once.
+type Stop = (Code, Option<Code>, bool);
+
+impl Graph {
+    // Every station a train on a line goes on to from a station, with the stations it stops at on the way,
+    // ending with that one, and the minutes it takes.
+    fn rides(&self, from: &Code, line: &Code) -> Vec<(Code, Vec<Code>, usize)> {
+        let mut minutes: HashMap<Code, usize> = HashMap::from([(from.clone(), 0)]);
+        let mut previous: HashMap<Code, Code> = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((0, from.clone()))]);
+        while let Some(Reverse((time, code))) = queue.pop() {
+            if time > minutes[&code] {
+                continue;
+            }
+            for (next, hop, next_line) in self.neighbours(&code) {
+                if next_line != *line || minutes.get(&next).is_some_and(|&best| best <= time + hop) {
+                    continue;
+                }
+                minutes.insert(next.clone(), time + hop);
+                previous.insert(next.clone(), code.clone());
+                queue.push(Reverse((time + hop, next)));
+            }
+        }
+
+        minutes
+            .into_iter()
+            .filter(|(code, _)| code != from)
+            .map(|(code, time)| {
+                let mut stops = vec![code.clone()];
+                while let Some(prev) = previous.get(stops.last().unwrap()).filter(|prev| *prev != from) {
+                    stops.push(prev.clone());
+                }
+                stops.reverse();
+                (code, stops, time)
+            })
+            .collect()
+    }
+
+    // What the cheapest journey between two stations costs, going around anything closed.
+    // A journey is made of rides, each on one line from getting on to getting off, and walks between stations.
+    // A ride between two stations in the fare table costs what the table says, any other is priced by the minute.
+    pub fn fare(&self, from: &Code, to: &Code) -> Option<Fare> {
+        if !self.stations.contains_key(from) || !self.stations.contains_key(to) {
+            return None;
+        }
+
+        let now = Utc::now();
+        let premium = |line: &Code| self.fares.premiums.get(line).copied().map_or(0, cents);
+
+        // cheapest first, and the quickest of those
+        let mut costs: HashMap<Stop, (u64, usize)> = HashMap::new();
+        let mut previous: HashMap<Stop, (Stop, Vec<Code>)> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        let origin: Stop = (from.clone(), None, false);
+        costs.insert(origin.clone(), (0, 0));
+        queue.push(Reverse(((0, 0), origin)));
+
+        while let Some(Reverse((cost, stop))) = queue.pop() {
+            if cost > costs[&stop] {
+                continue;
+            }
+
+            let (code, line, boarded) = &stop;
+            let (cents_so_far, time) = cost;
+            let mut moves = vec![]; // where to, the line, the stations passed on the way, the price and the minutes
+            let mut lines = vec![];
+            for (next, minutes, next_line) in self.neighbours(code) {
+                if next_line == WALK {
+                    moves.push((next.clone(), next_line, vec![next], 0, minutes));
+                } else if line.as_ref() != Some(&next_line) && !lines.contains(&next_line) {
+                    lines.push(next_line); // getting back on the line just taken would only split the same ride
+                }
+            }
+            for next_line in lines {
+                for (next, stops, minutes) in self.rides(code, &next_line) {
+                    let price = match self.fares.fixed(code, &next) {
+                        Some(fare) => cents(fare),
+                        None => {
+                            let base = if *boarded { 0 } else { cents(self.fares.base) };
+                            base + premium(&next_line) + cents(self.fares.per_minute * minutes as f64)
+                        }
+                    };
+                    moves.push((next, next_line.clone(), stops, price, minutes));
+                }
+            }
+
+            for (next, next_line, stops, price, minutes) in moves {
+                let transfer = self.transfers.between(code, line.as_ref(), &next_line);
+                let boarding = now + chrono::Duration::minutes((time + transfer) as i64);
+                let wait = self.wait(&next_line, boarding);
+
+                let next_cost = (cents_so_far + price, time + transfer + wait + minutes);
+                let next_stop = (next, Some(next_line.clone()), *boarded || next_line != WALK);
+                if costs.get(&next_stop).is_none_or(|&best| next_cost < best) {
+                    costs.insert(next_stop.clone(), next_cost);
+                    previous.insert(next_stop.clone(), (stop.clone(), stops));
+                    queue.push(Reverse((next_cost, next_stop)));
+                }
+            }
+        }
+
+        let (arrival, &(price, minutes)) = costs
+            .iter()
+            .filter(|((code, _, _), _)| code == to)
+            .min_by_key(|(_, cost)| **cost)?;
+
+        // walk back from the destination to the start, a ride at a time
+        let mut stations = vec![];
+        let mut lines = vec![];
+        let mut current = arrival;
+        while let Some((stop, stops)) = previous.get(current) {
+            for code in stops.iter().rev() {
+                stations.push(code.clone());
+                lines.extend(current.1.clone());
+            }
+            current = stop;
+        }
+        stations.push(from.clone());
+        stations.reverse();
+        lines.reverse();
+
+        Some(Fare {
+            fare: price as f64 / 100.0,
+            currency: self.fares.currency.clone(),
+            route: Route {
+                stations,
+                lines,
+                minutes,
+            },
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FareQuery {
+    from: String,
+    to: String,
+}
+
+// cheapest fare between two stations, e.g. /fare?from=TSW&to=CEN
+pub async fn get(Extension(game): Extension<Arc<RwLock<Game>>>, Query(query): Query<FareQuery>) -> impl IntoResponse {
+    let (Ok(from), Ok(to)) = (Code::from_str(&query.from), Code::from_str(&query.to)) else {
+        return Json(json!({"error": "Invalid station code"}));
+    };
+
+    let game = game.read().await;
+    for code in [&from, &to] {
+        if !game.graph.stations.contains_key(code) {
+            return Json(json!({"error": format!("Station {} not found", code)}));
+        }
+        if !game.graph.is_open(code) {
+            return Json(json!({"error": format!("Station {} is closed", code)}));
+        }
+    }
+
+    match game.graph.fare(&from, &to) {
+        Some(fare) => {
+            let mut json = json!(fare);
+            json["directions"] = json!(game.graph.directions(&fare.route));
+            Json(json)
+        }
+        None => Json(json!({"error": "No route between these stations"})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(code: &str) -> Code {
+        Code::from_str(code).unwrap()
+    }
+
+    // a slow line from A to C through B, an express straight there and a walk from D to A
+    fn graph() -> Graph {
+        let mut graph = Graph::parse_csv(
+            "A,Alpha,22.30,114.10,-,SLO
+B,Bravo,22.31,114.10,4,SLO
+C,Charlie,22.32,114.10,4,SLO
+,,,,,
+A,Alpha,22.30,114.10,-,EXP
+C,Charlie,22.32,114.10,3,EXP
+,,,,,
+D,Delta,22.30,114.11,-,WLK
+A,Alpha,22.30,114.10,2,WLK",
+        )
+        .unwrap();
+        graph.fares = Fares {
+            currency: "HKD".to_string(),
+            base: 2.0,
+            per_minute: 0.5,
+            premiums: HashMap::from([(code("EXP"), 10.0)]),
+            table: vec![],
+        };
+        graph
+    }
+
+    #[test]
+    fn priced_by_the_minute() {
+        let graph = graph();
+        let fare = graph.fare(&code("A"), &code("B")).unwrap();
+        assert_eq!(fare.fare, 4.0); // 2 to get on and 4 minutes at 0.5
+        assert_eq!(fare.currency, "HKD");
+        assert_eq!(fare.route.stations, vec![code("A"), code("B")]);
+        assert_eq!(fare.route.lines, vec![code("SLO")]);
+    }
+
+    #[test]
+    fn premium_lines_are_avoided_when_dearer() {
+        let mut graph = graph();
+        let fare = graph.fare(&code("A"), &code("C")).unwrap();
+        assert_eq!(fare.fare, 6.0); // the express would be 2 + 10 + 1.5
+        assert_eq!(fare.route.stations, vec![code("A"), code("B"), code("C")]);
+        assert_eq!(fare.route.lines, vec![code("SLO"), code("SLO")]);
+
+        graph.fares.premiums.clear();
+        let fare = graph.fare(&code("A"), &code("C")).unwrap();
+        assert_eq!(fare.fare, 3.5);
+        assert_eq!(fare.route.lines, vec![code("EXP")]);
+    }
+
+    #[test]
+    fn walking_is_free() {
+        let graph = graph();
+        assert_eq!(graph.fare(&code("D"), &code("A")).unwrap().fare, 0.0);
+        let fare = graph.fare(&code("D"), &code("B")).unwrap();
+        assert_eq!(fare.fare, 4.0);
+        assert_eq!(fare.route.stations, vec![code("D"), code("A"), code("B")]);
+        assert_eq!(fare.route.lines, vec![code("WLK"), code("SLO")]);
+    }
+
+    #[test]
+    fn table_fares_follow_the_route() {
+        let mut graph = graph();
+        graph.fares.table = vec![(code("C"), code("A"), 20.0)];
+
+        // every ride from A to C costs what the table says, whichever line
+        let fare = graph.fare(&code("A"), &code("C")).unwrap();
+        assert_eq!(fare.fare, 20.0);
+
+        // walking to A first doesn't get around it, and the route is the one that was priced
+        let fare = graph.fare(&code("D"), &code("C")).unwrap();
+        assert_eq!(fare.fare, 20.0);
+        assert_eq!(fare.route.stations.first(), Some(&code("D")));
+        assert_eq!(fare.route.lines.first(), Some(&code("WLK")));
+
+        // a cheaper table fare is taken over the minutes
+        graph.fares.table = vec![(code("A"), code("C"), 1.0)];
+        assert_eq!(graph.fare(&code("D"), &code("C")).unwrap().fare, 1.0);
+    }
+
+    #[test]
+    fn closed_lines_are_avoided() {
+        let mut graph = graph();
+        graph.mask.lines.insert(code("SLO"));
+        let fare = graph.fare(&code("A"), &code("C")).unwrap();
+        assert_eq!(fare.fare, 13.5);
+        assert_eq!(fare.route.lines, vec![code("EXP")]);
+        assert!(graph.fare(&code("A"), &code("B")).is_none());
+    }
+
+    #[test]
+    fn airport_express_from_central() {
+        let graph = Graph::from_csv();
+        let from_hong_kong = graph.fare(&code("HOK"), &code("AIR")).unwrap();
+        let from_central = graph.fare(&code("CEN"), &code("AIR")).unwrap();
+        // no dearer than the published fare, and walking over from Central is the same journey
+        assert!(from_hong_kong.fare <= 115.0);
+        assert_eq!(from_central.fare, from_hong_kong.fare);
+        assert_eq!(&from_central.route.stations[..2], &[code("CEN"), code("HOK")]);
+        assert_eq!(from_central.route.lines[0], code("WLK"));
+        assert_eq!(from_central.route.lines.last(), Some(&code("AEL")));
+    }
+
+    #[test]
+    fn unknown_stations() {
+        let graph = graph();
+        assert!(graph.fare(&code("A"), &code("Z")).is_none());
+        assert_eq!(graph.fare(&code("A"), &code("A")).unwrap().fare, 0.0);
+    }
+}
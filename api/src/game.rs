@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::Arc};
 
-use crate::{db::Db, graph::Graph, player::Player, socket::Tx, state_machine::{Event}, states::{State, GameState}, teams::Team};
+use crate::{db::Db, graph::Graph, player::{Player, PlayerType}, scores::Scoreboard, settings::Settings, spatial::Grid, socket::Tx, state_machine::{Event}, states::{GameState, Sighting}, teams::{Team, TeamType}};
 use axum::{extract::ws::Message};
 use tokio::sync::{mpsc, RwLock};
 
@@ -9,29 +9,60 @@ pub struct Game {
     pub players: HashMap<String, Player>,
     pub teams: HashMap<String, Team>,
     pub hidden_teams: HashMap<String, Team>, // teams that have already hidden once
+    pub next_hider: Option<String>, // team picked by an admin to hide next
+    pub scores: Scoreboard,
+    pub round: usize, // current round of the session, 0 before the first one
     pub connections: HashMap<String, Tx>, //username agains string
+    pub positions: Grid<String>, // last known location of every player, by username
+    pub sighting: Option<Sighting>, // where the hiders were last revealed this round
     pub graph: Graph,
     pub tx: mpsc::Sender<Event>, // send event to the gamestate
-    pub state: Arc<RwLock<GameState>>
+    pub state: Arc<RwLock<GameState>>,
+    pub settings: Settings,
 }
 
 impl Game {
-    pub async fn new(db_inst: &str, secret: &str, state: Arc<RwLock<GameState>>) -> (Arc<RwLock<Self>>, mpsc::Receiver<Event>) {
+    pub async fn new(db_inst: &str, secret: &str, state: Arc<RwLock<GameState>>, settings: Settings) -> (Arc<RwLock<Self>>, mpsc::Receiver<Event>) {
         let db = Db::new(db_inst, secret).await;
+        let players = db.init().await;
+        let (game, rx) = Game::with(db, players, state, settings);
+        (Arc::new(RwLock::new(game)), rx)
+    }
+
+    fn with(db: Db, players: HashMap<String, Player>, state: Arc<RwLock<GameState>>, settings: Settings) -> (Self, mpsc::Receiver<Event>) {
         let (tx, rx) = mpsc::channel(2);
+        let mut graph = Graph::load_or_default(settings.network.as_deref());
+        graph.add_walks(&settings);
 
         let game = Game {
-            players: db.init().await,
+            players,
             db,
             teams: HashMap::new(),
             hidden_teams: HashMap::new(),
+            next_hider: None,
+            scores: Scoreboard::default(),
+            round: 0,
             connections: HashMap::new(),
-            graph: Graph::from_csv(),
+            positions: Grid::default(),
+            sighting: None,
+            graph,
             tx,
             state,
+            settings,
         };
+        (game, rx)
+    }
 
-        (Arc::new(RwLock::new(game)), rx)
+    // A game in the lobby with some players and no database behind it, for tests.
+    #[cfg(test)]
+    pub async fn offline(usernames: &[&str]) -> (Self, mpsc::Receiver<Event>) {
+        let players = usernames
+            .iter()
+            .map(|username| (username.to_string(), Player::new(username.to_string(), String::new())))
+            .collect();
+        use crate::states::{LobbyState, State};
+        let state = Arc::new(RwLock::new(GameState::Lobby(LobbyState::new())));
+        Game::with(Db::offline().await, players, state, Settings::default())
     }
 
     pub fn get_player(&self, username: &str) -> Result<&Player, String> {
@@ -53,6 +84,7 @@ impl Game {
     pub async fn remove_player(&mut self, username: &str) -> Result<(), String> {
         if self.players.contains_key(username) {
             self.players.remove(username);
+            self.positions.remove(&username.to_string());
         } else {
             return Err("Player not found".to_string())
         }
@@ -95,4 +127,183 @@ impl Game {
         self.teams.insert(team.name.clone(), team);
         Ok(())
     }
+
+    // Start a fresh game session, every team gets to hide again and scores are wiped.
+    pub fn new_session(&mut self) {
+        self.hidden_teams.clear();
+        self.scores.clear();
+        self.round = 0;
+    }
+
+    // Undo the bookkeeping of the current round so it can be played again by the same hiders.
+    pub fn restart_round(&mut self) {
+        if let Some(hider) = self.hider_team().map(|team| team.name.clone()) {
+            self.hidden_teams.remove(&hider);
+            self.next_hider = Some(hider);
+        }
+        self.scores.discard(self.round);
+        self.round = self.round.saturating_sub(1);
+    }
+
+    // Whether another round can be played this session.
+    pub fn has_next_round(&self) -> bool {
+        self.next_hider.is_some()
+            || self.teams.keys().any(|name| !self.hidden_teams.contains_key(name))
+    }
+
+    pub fn hider_team(&self) -> Option<&Team> {
+        self.teams.values().find(|team| matches!(team.ttype, TeamType::Hider))
+    }
+
+    // Pick the team to hide this round: the admin's choice if there is one,
+    // otherwise the first team (by name) that has not hidden yet this game.
+    pub fn pick_hiders(&mut self) -> Option<String> {
+        if let Some(name) = self.next_hider.take() {
+            if self.teams.contains_key(&name) {
+                return Some(name);
+            }
+            tracing::error!("Chosen hider team {} no longer exists", name);
+        }
+
+        let mut names: Vec<&String> = self
+            .teams
+            .keys()
+            .filter(|name| !self.hidden_teams.contains_key(*name))
+            .collect();
+        names.sort();
+        names.first().map(|name| name.to_string())
+    }
+
+    // Make `hider` the hiding team and everyone else a seeker, for both the teams and their players.
+    pub fn assign_roles(&mut self, hider: &str) {
+        for team in self.teams.values_mut() {
+            let (ttype, ptype) = if team.name == hider {
+                (TeamType::Hider, PlayerType::Hider)
+            } else {
+                (TeamType::Seeker, PlayerType::PrimarySeeker)
+            };
+            team.update_type(ttype);
+
+            for username in team.players.iter() {
+                if let Some(player) = self.players.get_mut(username) {
+                    player.ptype = ptype.clone();
+                }
+            }
+        }
+
+        if let Some(team) = self.teams.get(hider) {
+            self.hidden_teams.insert(hider.to_string(), team.clone());
+        }
+    }
+
+    // Everyone goes back to spectating between sessions.
+    pub fn reset_roles(&mut self) {
+        for team in self.teams.values_mut() {
+            team.update_type(TeamType::Spectator);
+        }
+        for player in self.players.values_mut() {
+            if !matches!(player.ptype, PlayerType::Admin) {
+                player.ptype = PlayerType::Spectator;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scores::RoundResult;
+
+    // a game with a team of one player for each name, the player called the same as their team
+    async fn game(teams: &[&str]) -> Game {
+        let (mut game, _) = Game::offline(teams).await;
+        for name in teams {
+            let mut team = Team::new(name.to_string());
+            team.add_player(name.to_string());
+            game.new_team(team).await.unwrap();
+        }
+        game
+    }
+
+    fn result(round: usize, hider: &str, survived: u64) -> RoundResult {
+        RoundResult {
+            round,
+            hider: hider.to_string(),
+            survived,
+            caught: true,
+            caught_by: None,
+            capture_station: None,
+        }
+    }
+
+    // what the hide phase does at the start of every round
+    fn play(game: &mut Game) -> Option<String> {
+        let hider = game.pick_hiders()?;
+        game.round += 1;
+        game.assign_roles(&hider);
+        Some(hider)
+    }
+
+    #[tokio::test]
+    async fn teams_hide_in_order_of_their_names() {
+        let mut game = game(&["bravo", "alpha", "charlie"]).await;
+        assert_eq!(play(&mut game).as_deref(), Some("alpha"));
+        assert!(matches!(game.teams["alpha"].ttype, TeamType::Hider));
+        assert!(matches!(game.teams["bravo"].ttype, TeamType::Seeker));
+        assert!(matches!(game.players["alpha"].ptype, PlayerType::Hider));
+        assert!(game.players["charlie"].ptype.is_seeker());
+
+        assert_eq!(play(&mut game).as_deref(), Some("bravo"));
+        assert!(matches!(game.players["alpha"].ptype, PlayerType::PrimarySeeker));
+    }
+
+    #[tokio::test]
+    async fn an_admin_can_pick_the_next_hiders() {
+        let mut game = game(&["alpha", "bravo", "charlie"]).await;
+        game.next_hider = Some("charlie".to_string());
+        assert_eq!(play(&mut game).as_deref(), Some("charlie"));
+        assert_eq!(game.next_hider, None);
+        assert_eq!(play(&mut game).as_deref(), Some("alpha"));
+
+        // a team that has gone since it was picked is skipped
+        game.next_hider = Some("delta".to_string());
+        assert_eq!(play(&mut game).as_deref(), Some("bravo"));
+    }
+
+    #[tokio::test]
+    async fn no_next_round_once_every_team_has_hidden() {
+        let mut game = game(&["alpha", "bravo"]).await;
+        assert!(game.has_next_round());
+        play(&mut game);
+        assert!(game.has_next_round());
+        play(&mut game);
+        assert!(!game.has_next_round());
+        assert_eq!(game.pick_hiders(), None);
+
+        // unless an admin wants a team to go again
+        game.next_hider = Some("alpha".to_string());
+        assert!(game.has_next_round());
+
+        game.next_hider = None;
+        game.new_session();
+        assert!(game.has_next_round());
+        assert_eq!(game.round, 0);
+    }
+
+    #[tokio::test]
+    async fn restarting_a_round_replays_it_with_the_same_hiders() {
+        let mut game = game(&["alpha", "bravo"]).await;
+        play(&mut game);
+        game.scores.record(result(1, "alpha", 300));
+        play(&mut game);
+        game.scores.record(result(2, "bravo", 200));
+
+        game.restart_round();
+        assert_eq!(game.round, 1);
+        assert!(!game.hidden_teams.contains_key("bravo"));
+        assert_eq!(game.scores.rounds.len(), 1);
+        assert!(game.has_next_round());
+        assert_eq!(play(&mut game).as_deref(), Some("bravo"));
+        assert_eq!(game.round, 2);
+    }
 }
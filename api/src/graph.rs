@@ -1,10 +1,146 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
 
-use crate::station::{Code, Connection, Station};
+use axum::{
+    extract::{ws::Message, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::{
+    disruption::Mask,
+    fare::Fares,
+    game::Game,
+    line::{Line, LineInfo},
+    location::{Bounds, Location, Projection},
+    settings::Settings,
+    spatial::Grid,
+    states::Phase,
+    station::{Code, Connection, Platform, Station, WALK},
+    validation::Issue,
+};
+
+// A journey through the network, from the first station to the last.
+#[derive(Debug, Clone, Serialize)]
+pub struct Route {
+    pub stations: Vec<Code>,
+    pub lines: Vec<Code>, // line taken between each pair of stations
+    pub minutes: usize,   // including the time spent changing lines and waiting for trains
+}
+
+// Where the hiders could be, given where they were last seen and how long ago.
+#[derive(Debug, Clone, Serialize)]
+pub struct Zone {
+    pub from: Vec<Code>, // stations the hiders were last seen at
+    pub minutes: usize,  // since they were seen
+    pub stations: Vec<ZoneStation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ZoneStation {
+    pub code: Code,
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub arrival: usize, // earliest the hiders could have got here, in minutes after they were seen
+}
+
+// Local time for data that doesn't say otherwise.
+fn hong_kong() -> FixedOffset {
+    FixedOffset::east_opt(8 * 3600).unwrap()
+}
+
+// A station, and the line it was arrived on.
+type Stop = (Code, Option<Code>);
 
 pub struct Graph {
     pub stations: HashMap<Code, Station>,
     pub connections: HashSet<Connection>,
+    pub transfers: Transfers,
+    pub fares: Fares,
+    pub lines: HashMap<Code, Line>,
+    pub load_issues: Vec<Issue>, // problems found while reading the data, see `validate`
+    pub timezone: FixedOffset,   // local time of the network, for how often trains run
+    pub projection: Projection,  // where stations go on the map image
+    pub bounds: Bounds,
+    pub index: Grid<Code>, // where the stations are, for proximity checks
+    pub walks: HashSet<Connection>, // generated walking links between nearby stations, see `add_walks`
+    pub walking: bool,              // whether journeys can walk between stations at all
+    pub mask: Mask,                 // what is closed for the current game
+}
+
+// Minutes it takes to change trains at a station. Walking links between stations take their own minutes instead,
+// so there is nothing extra for stepping on or off a walk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfers {
+    pub default: usize,
+    pub stations: HashMap<Code, usize>, // stations where changing is quicker or slower than the default
+}
+
+impl Default for Transfers {
+    fn default() -> Self {
+        let stations = [
+            ("ADM", 2), // cross platform between the Tsuen Wan and Island lines
+            ("MEF", 6), // Tuen Ma and Tsuen Wan line platforms are in separate buildings
+            ("KOT", 6), // long corridor between the East Rail and Kwun Tong lines
+            ("NAC", 4),
+            ("TAW", 2),
+            ("HUH", 3),
+            ("PRE", 2),
+            ("MOK", 2),
+            ("YAT", 2),
+            ("TIK", 1),
+            ("NOP", 4),
+            ("QUB", 5), // deep Tseung Kwan O line platforms
+            ("LAK", 2),
+            ("SUN", 2),
+        ]
+        .into_iter()
+        .map(|(code, minutes)| (Code::from_str(code).unwrap(), minutes))
+        .collect();
+
+        Transfers {
+            default: 3,
+            stations,
+        }
+    }
+}
+
+impl Transfers {
+    pub fn at(&self, code: &Code) -> usize {
+        self.stations.get(code).copied().unwrap_or(self.default)
+    }
+
+    // Minutes to go from arriving on one line to leaving on another at a station, nothing for staying on
+    // the same line, for the start of a journey or for either side of a walk.
+    pub fn between(&self, code: &Code, from: Option<&Code>, to: &Code) -> usize {
+        match from {
+            Some(from) if from != to && *from != WALK && *to != WALK => self.at(code),
+            _ => 0,
+        }
+    }
+}
+
+// Settings that the csv and the older ron files have nowhere to put, from a file of their own next to the data.
+fn read_beside<T: DeserializeOwned>(folder: &Path, name: &str) -> Result<Option<T>, String> {
+    let path = folder.join(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let ron = fs::read_to_string(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    ron::de::from_str(&ron)
+        .map(Some)
+        .map_err(|error| format!("{}: {}", path.display(), error))
 }
 
 impl Graph {
@@ -12,131 +148,756 @@ impl Graph {
         Graph {
             stations: HashMap::new(),
             connections: HashSet::new(),
+            transfers: Transfers::default(),
+            fares: Fares::default(),
+            lines: HashMap::new(),
+            load_issues: Vec::new(),
+            timezone: hong_kong(),
+            projection: Projection::default(),
+            bounds: Bounds::default(),
+            index: Grid::default(),
+            walks: HashSet::new(),
+            walking: true,
+            mask: Mask::default(),
         }
     }
 
+    // Put every station in the spatial index, once they are all loaded.
+    fn indexed(mut self) -> Self {
+        for station in self.stations.values() {
+            self.index.insert(station.code.clone(), station.latitude, station.longitude);
+        }
+        self
+    }
+
     pub fn from_csv() -> Self {
-        // Derive the graph from the csv.
-        let mut lines: Vec<&str> = CSV.lines().collect();
-        lines.retain(|line| !line.trim().is_empty()); // parse csv into a string and remove all empty lines.
+        Graph::parse_csv(CSV).expect("the built in network should always parse")
+    }
 
+    // Derive the graph from a csv of `code,name,latitude,longitude,minutes from previous row,line`.
+    // Rows of a line are listed in order, a blank row or a minutes of `-` starts a new stretch of line.
+    pub fn parse_csv(csv: &str) -> Result<Self, String> {
         let mut stations: HashMap<Code, Station> = HashMap::new(); // empty datastructure
         let mut connections = HashSet::<Connection>::new();
-        let mut prev_station: Option<Station> = None;
+        let mut load_issues = Vec::new();
+        let mut prev_station: Option<(Code, Code)> = None; // code and line of the previous row
+        let mut segments: Vec<(Code, Vec<Code>)> = vec![]; // each stretch of line, in order
 
-        for line in lines {
+        for (number, line) in csv.lines().enumerate() {
             // for each line in the csv
-            let cols: Vec<&str> = line.split(',').collect(); // split into each column
+            let row = number + 1;
+            let cols: Vec<&str> = line.split(',').map(|col| col.trim()).collect(); // split into each column
 
-            if cols[0].is_empty() {
+            if cols.iter().all(|col| col.is_empty()) {
                 // if we notice that the line is changing
                 prev_station = None; // don't create a connection to the previous station
                 continue; // skip
             }
+            if cols.len() != 6 {
+                return Err(format!("row {}: expected 6 columns, found {}", row, cols.len()));
+            }
+            if cols[2].parse::<f64>().is_err() && stations.is_empty() && prev_station.is_none() {
+                continue; // header row
+            }
 
             // get the details of the Station.
-            let code = Code(
-                cols[0].chars().nth(0).unwrap(),
-                cols[0].chars().nth(1).unwrap(),
-                cols[0].chars().nth(2).unwrap(),
-            );
-            let line_code = Code(
-                cols[5].chars().next().unwrap(),
-                cols[5].chars().nth(1).unwrap(),
-                cols[5].chars().nth(2).unwrap(),
-            );
+            let code = Code::from_str(cols[0]).map_err(|_| format!("row {}: invalid station code {}", row, cols[0]))?;
+            let line_code = Code::from_str(cols[5]).map_err(|_| format!("row {}: invalid line code {}", row, cols[5]))?;
+            let coordinate = |col: &str| col.parse::<f64>().map_err(|_| format!("row {}: invalid coordinate {}", row, col));
 
-            // Create the Station
-            let station = Station {
-                code,
-                name: cols[1].to_string(),
-                latitude: cols[2].parse::<f64>().unwrap(),
-                longitude: cols[3].parse::<f64>().unwrap(),
-                line_code,
+            let platform = Platform {
+                line_code: line_code.clone(),
+                latitude: coordinate(cols[2])?,
+                longitude: coordinate(cols[3])?,
             };
 
-            stations.insert(station.code, station.clone()); // Add the station to the hashmap
+            // Interchanges appear once per line, so add the line to the station if we have already seen it.
+            match stations.get_mut(&code) {
+                Some(station) => {
+                    if station.name != cols[1] {
+                        load_issues.push(Issue::ConflictingNames {
+                            code: code.clone(),
+                            names: (station.name.clone(), cols[1].to_string()),
+                        });
+                    }
+                    station.add_platform(platform);
+                }
+                None => {
+                    let station = Station {
+                        code: code.clone(),
+                        name: cols[1].to_string(),
+                        latitude: platform.latitude,
+                        longitude: platform.longitude,
+                        lines: vec![platform],
+                    };
+                    stations.insert(code.clone(), station); // Add the station to the hashmap
+                }
+            }
 
-            if let Some(prev_station) = prev_station {
+            let mut continues = false;
+            if let Some((prev_code, prev_line)) = prev_station.take().filter(|_| cols[4] != "-") {
                 // if the previous station exists
-                if prev_station.line_code == line_code {
-                    // check if the line codes are the same
-                    let minutes = cols[4].parse::<usize>().unwrap_or(0); // if so, create a connection and add it to the hashset.
-                    let connection = Connection(prev_station.code, station.code, minutes);
+                if prev_line == line_code {
+                    continues = true;
+                    // check if the line codes are the same, if so, create a connection and add it to the hashset.
+                    let minutes = match cols[4] {
+                        "" => {
+                            load_issues.push(Issue::MissingTime {
+                                from: prev_code.clone(),
+                                to: code.clone(),
+                                line: line_code.clone(),
+                            });
+                            0
+                        }
+                        minutes => minutes
+                            .parse::<usize>()
+                            .map_err(|_| format!("row {}: invalid minutes {}", row, minutes))?,
+                    };
+
+                    let connection = Connection(prev_code, code.clone(), minutes, line_code.clone());
+                    match connections.get(&connection) {
+                        Some(existing) if existing.2 != minutes => load_issues.push(Issue::Asymmetric {
+                            from: connection.0.clone(),
+                            to: connection.1.clone(),
+                            line: connection.3.clone(),
+                            minutes: (existing.2, minutes),
+                        }),
+                        _ => {}
+                    }
                     connections.insert(connection);
                 }
             }
-            prev_station = Some(station); // before recursing, set the previous station to the current one.
+            match segments.last_mut() {
+                Some((_, segment)) if continues => segment.push(code.clone()),
+                _ => segments.push((line_code.clone(), vec![code.clone()])),
+            }
+            prev_station = Some((code, line_code)); // before recursing, set the previous station to the current one.
         }
-        Graph {
+
+        Ok(Graph {
             stations,
             connections,
-        } // return the Graph
+            transfers: Transfers::default(),
+            fares: Fares::default(),
+            lines: Line::from_segments(segments),
+            load_issues,
+            timezone: hong_kong(),
+            projection: Projection::default(),
+            bounds: Bounds::default(),
+            index: Grid::default(),
+            walks: HashSet::new(),
+            walking: true,
+            mask: Mask::default(),
+        }
+        .indexed()) // return the Graph
     }
 
     pub fn from_ron() -> Self {
-        let stations: HashMap<Code, Station> = ron::de::from_str(STATIONS).unwrap();
-        let connections: HashSet<Connection> = ron::de::from_str(CONNECTIONS).unwrap();
-        Graph {
+        Graph::parse_ron(STATIONS, CONNECTIONS).expect("the built in network should always parse")
+    }
+
+    // Read the network from a file, csv or ron depending on the extension.
+    // A ron file is either a whole network, see `parse_network`, or the older data split in two,
+    // `stations.ron` and `connections.ron`, in which case the path can be either file or their folder.
+    // Csv data is always taken to be the MTR, as it has nowhere to put a projection.
+    // For those two formats transfer times and fares can go in a `transfers.ron` and `fares.ron` in the same folder.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let read = |path: &Path| fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error));
+
+        let folder = if path.is_dir() { path } else { path.parent().unwrap_or(Path::new(".")) };
+        let mut graph = if path.is_dir() {
+            Graph::parse_ron(&read(&path.join("stations.ron"))?, &read(&path.join("connections.ron"))?)?
+        } else {
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("csv") => Graph::parse_csv(&read(path)?)?,
+                Some("ron") => match path.file_name().and_then(|name| name.to_str()) {
+                    Some("stations.ron" | "connections.ron") => {
+                        Graph::parse_ron(&read(&folder.join("stations.ron"))?, &read(&folder.join("connections.ron"))?)?
+                    }
+                    _ => return Graph::parse_network(&read(path)?), // has its own transfers and fares
+                },
+                _ => return Err(format!("{}: expected a .csv or .ron file", path.display())),
+            }
+        };
+
+        if let Some(transfers) = read_beside(folder, "transfers.ron")? {
+            graph.transfers = transfers;
+        }
+        if let Some(fares) = read_beside(folder, "fares.ron")? {
+            graph.fares = fares;
+        }
+        Ok(graph)
+    }
+
+    // The network at the configured path, or the built in one if there is none or it can't be read.
+    pub fn load_or_default(path: Option<&str>) -> Self {
+        match path.map(|path| Graph::load(Path::new(path))) {
+            Some(Ok(graph)) => graph,
+            Some(Err(error)) => {
+                tracing::warn!("Could not load the network, using the built in one: {}", error);
+                Graph::from_csv()
+            }
+            None => Graph::from_csv(),
+        }
+    }
+
+    // A whole network in one ron file, with the map it is drawn on. See `util/singapore.ron` for an example.
+    // Stations are on every line that has a connection to them.
+    pub fn parse_network(ron: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct Network {
+            projection: Projection,
+            bounds: Bounds,
+            #[serde(default)]
+            transfers: Option<Transfers>,
+            #[serde(default)]
+            fares: Option<Fares>,
+            #[serde(default)]
+            lines: Vec<LineInfo>,
+            #[serde(default = "utc_offset")]
+            utc_offset: i32, // hours ahead of UTC local time is, for the service patterns
+            stations: Vec<NetworkStation>,
+            connections: Vec<Connection>,
+        }
+
+        #[derive(Deserialize)]
+        struct NetworkStation {
+            code: Code,
+            name: String,
+            latitude: f64,
+            longitude: f64,
+        }
+
+        fn utc_offset() -> i32 {
+            8 // Hong Kong
+        }
+
+        let network: Network = ron::de::from_str(ron).map_err(|error| error.to_string())?;
+        let timezone = FixedOffset::east_opt(network.utc_offset * 3600)
+            .ok_or_else(|| format!("invalid utc offset {}", network.utc_offset))?;
+        let mut load_issues = Vec::new();
+        let mut stations: HashMap<Code, Station> = HashMap::new();
+        for station in network.stations {
+            if let Some(existing) = stations.get(&station.code) {
+                load_issues.push(Issue::ConflictingNames {
+                    code: station.code.clone(),
+                    names: (existing.name.clone(), station.name.clone()),
+                });
+                continue;
+            }
+            stations.insert(
+                station.code.clone(),
+                Station {
+                    code: station.code,
+                    name: station.name,
+                    latitude: station.latitude,
+                    longitude: station.longitude,
+                    lines: vec![],
+                },
+            );
+        }
+
+        let mut connections = HashSet::new();
+        for connection in network.connections {
+            for code in [&connection.0, &connection.1] {
+                if let Some(station) = stations.get_mut(code) {
+                    let platform = Platform {
+                        line_code: connection.3.clone(),
+                        latitude: station.latitude,
+                        longitude: station.longitude,
+                    };
+                    station.add_platform(platform);
+                }
+            }
+            if let Some(Connection(from, to, minutes, line)) = connections.get(&connection) {
+                if *minutes != connection.2 {
+                    load_issues.push(Issue::Asymmetric {
+                        from: from.clone(),
+                        to: to.clone(),
+                        line: line.clone(),
+                        minutes: (*minutes, connection.2),
+                    });
+                }
+            }
+            connections.insert(connection);
+        }
+
+        // names and colours for the lines, where the file has them
+        let mut lines = Line::from_connections(&connections);
+        for info in network.lines {
+            if let Some(line) = lines.get_mut(&info.code) {
+                line.name = info.name;
+                line.colour = info.colour;
+                line.headway = info.headway.unwrap_or(line.headway);
+            }
+        }
+
+        Ok(Graph {
             stations,
             connections,
+            lines,
+            transfers: network.transfers.unwrap_or_default(),
+            fares: network.fares.unwrap_or_default(),
+            load_issues,
+            timezone,
+            projection: network.projection,
+            bounds: network.bounds,
+            index: Grid::default(),
+            walks: HashSet::new(),
+            walking: true,
+            mask: Mask::default(),
         }
+        .indexed())
     }
 
-    pub fn dfs(&self, start_code: Code, max_time: usize) -> Vec<Station> {
-        // Ensure the start station exists in the graph.
-        let start_station = match self.stations.get(&start_code) {
-            Some(station) => station,
-            None => return vec![], // If start station doesn't exist, return an empty list.
-        };
+    pub fn parse_ron(stations: &str, connections: &str) -> Result<Self, String> {
+        // the ron data mostly predates interchanges, a station that is on more than one line lists them all
+        #[derive(Deserialize)]
+        struct RonStation {
+            code: Code,
+            name: String,
+            latitude: f64,
+            longitude: f64,
+            line_code: Code,
+            #[serde(default)]
+            lines: Vec<Code>, // every line at an interchange, first the ones to prefer for its connections
+        }
 
-        // Visited set to avoid revisiting stations.
-        let mut visited = HashSet::new();
-        visited.insert(start_code);
+        let ron_stations: HashMap<Code, RonStation> =
+            ron::de::from_str(stations).map_err(|error| format!("stations: {}", error))?;
+        let stations: HashMap<Code, Station> = ron_stations
+            .into_iter()
+            .map(|(code, station)| {
+                let line_codes = if station.lines.is_empty() { vec![station.line_code] } else { station.lines };
+                let platforms = line_codes
+                    .into_iter()
+                    .map(|line_code| Platform {
+                        line_code,
+                        latitude: station.latitude,
+                        longitude: station.longitude,
+                    })
+                    .collect();
+                let station = Station {
+                    code: station.code,
+                    name: station.name,
+                    latitude: station.latitude,
+                    longitude: station.longitude,
+                    lines: platforms,
+                };
+                (code, station)
+            })
+            .collect();
+        // neither do the connections know their line, so use the first line of one station the other is on as well
+        let ron_connections: Vec<(Code, Code, usize)> =
+            ron::de::from_str(connections).map_err(|error| format!("connections: {}", error))?;
+        let mut connections = HashSet::new();
+        let mut load_issues = Vec::new();
+        for (a, b, minutes) in ron_connections {
+            let line_code = match (stations.get(&a), stations.get(&b)) {
+                (Some(from), Some(to)) => from.line_codes().into_iter().find(|line_code| to.is_on_line(line_code)),
+                // kept for `validate` to report the missing station
+                (Some(station), None) | (None, Some(station)) => station.line_codes().into_iter().next(),
+                (None, None) => None,
+            };
+            match line_code {
+                Some(line_code) => {
+                    connections.insert(Connection(a, b, minutes, line_code));
+                }
+                None => load_issues.push(Issue::NoCommonLine { from: a, to: b }),
+            }
+        }
 
-        // Stack to simulate DFS. Each entry contains the current station and the time spent to reach it.
-        let mut stack = vec![(start_station.clone(), 0)];
-        let mut result = vec![];
+        Ok(Graph {
+            stations,
+            lines: Line::from_connections(&connections),
+            connections,
+            transfers: Transfers::default(),
+            fares: Fares::default(),
+            load_issues,
+            timezone: hong_kong(),
+            projection: Projection::default(),
+            bounds: Bounds::default(),
+            index: Grid::default(),
+            walks: HashSet::new(),
+            walking: true,
+            mask: Mask::default(),
+        }
+        .indexed())
+    }
 
-        // DFS loop
-        while let Some((current_station, current_time)) = stack.pop() {
-            // Add the current station to the result if it’s not the starting station.
-            if current_station != *start_station {
-                result.push(current_station.clone());
+    // Link every pair of stations within walking distance that the data doesn't already connect,
+    // taking as many minutes as walking there in a straight line would.
+    pub fn add_walks(&mut self, settings: &Settings) {
+        self.walking = settings.walking;
+        self.walks.clear();
+        if settings.walking_speed <= 0.0 {
+            return;
+        }
+
+        for station in self.stations.values() {
+            let location = Location::new(station.latitude, station.longitude);
+            for (code, distance) in self.index.within(&location, settings.walk_distance) {
+                let connected = self.connections.iter().any(|connection| *connection == station.code && *connection == *code);
+                if *code <= station.code || connected {
+                    continue; // each pair once, and only where there is no link already
+                }
+                let minutes = (distance / settings.walking_speed).ceil().max(1.0) as usize;
+                self.walks.insert(Connection(station.code.clone(), code.clone(), minutes, Code::walk()));
             }
+        }
+    }
 
-            // Iterate over all connections to the current station.
-            for connection in self.connections.iter() {
-                // Check if the connection involves the current station.
-                if connection == &current_station.code {
-                    // Determine the next station in the connection.
-                    let next_code = if connection.0 == current_station.code {
-                        connection.1
-                    } else {
-                        connection.0
-                    };
+    // Every connection out of a station, as the station it leads to, the minutes it takes and its line.
+    // Includes the generated walks, leaves out anything closed, and every walk, from the data or generated,
+    // when walking is turned off.
+    pub fn neighbours(&self, code: &Code) -> Vec<(Code, usize, Code)> {
+        self.connections
+            .iter()
+            .chain(self.walks.iter())
+            .filter(|connection| self.walking || connection.3 != WALK)
+            .filter(|connection| *connection == code && !self.mask.closes(connection))
+            .map(|connection| (connection.other(code).clone(), connection.2, connection.3.clone()))
+            .collect()
+    }
+
+    // Expected minutes waiting for a train on a line, at some time.
+    pub fn wait(&self, line_code: &Code, at: DateTime<Utc>) -> usize {
+        self.lines
+            .get(line_code)
+            .map_or(0, |line| line.headway.wait(at.with_timezone(&self.timezone)))
+    }
 
-                    // Skip if we’ve already visited this station.
-                    if visited.contains(&next_code) {
-                        continue;
+    // Dijkstra from a station over (station, line being ridden), since changing lines costs the transfer time
+    // and boarding a line means waiting for the next train, which depends on the time of day.
+    // Gives the minimal minutes to every stop reached within the limit, and the stop each one was reached from.
+    fn dijkstra(
+        &self,
+        start: &Code,
+        limit: Option<usize>,
+        departure: DateTime<Utc>,
+    ) -> (HashMap<Stop, usize>, HashMap<Stop, Stop>) {
+        let mut times: HashMap<Stop, usize> = HashMap::new();
+        let mut previous: HashMap<Stop, Stop> = HashMap::new();
+        if !self.stations.contains_key(start) {
+            return (times, previous);
+        }
+
+        let mut queue = BinaryHeap::new();
+        let origin: Stop = (start.clone(), None); // not on any line yet, so no transfer for the first line
+        times.insert(origin.clone(), 0);
+        queue.push(Reverse((0, origin)));
+
+        while let Some(Reverse((time, stop))) = queue.pop() {
+            if time > times[&stop] {
+                continue; // already found a faster way here
+            }
+
+            let (code, line) = &stop;
+            for (next, minutes, next_line) in self.neighbours(code) {
+                let transfer = self.transfers.between(code, line.as_ref(), &next_line);
+                let wait = match line {
+                    Some(line) if *line == next_line => 0, // staying on the train
+                    _ => {
+                        let boarding = departure + chrono::Duration::minutes((time + transfer) as i64);
+                        self.wait(&next_line, boarding)
                     }
+                };
+                let next_time = time + transfer + wait + minutes;
+                if limit.is_some_and(|limit| next_time > limit) {
+                    continue;
+                }
 
-                    // Calculate the new time after traveling this connection.
-                    let new_time = current_time + connection.2;
+                let next_stop = (next, Some(next_line));
+                if times.get(&next_stop).is_none_or(|&best| next_time < best) {
+                    times.insert(next_stop.clone(), next_time);
+                    previous.insert(next_stop.clone(), stop.clone());
+                    queue.push(Reverse((next_time, next_stop)));
+                }
+            }
+        }
+        (times, previous)
+    }
 
-                    // If the new time is within the limit, continue DFS from the next station.
-                    if new_time <= max_time {
-                        if let Some(next_station) = self.stations.get(&next_code) {
-                            visited.insert(next_code);
-                            stack.push((next_station.clone(), new_time));
-                        }
+    // Fastest time to each station, whichever line it was reached on.
+    fn fastest(times: HashMap<Stop, usize>) -> HashMap<Code, usize> {
+        let mut fastest: HashMap<Code, usize> = HashMap::new();
+        for ((code, _), time) in times {
+            let best = fastest.entry(code).or_insert(time);
+            *best = (*best).min(time);
+        }
+        fastest
+    }
+
+    // The fastest way between two stations.
+    pub fn shortest_path(&self, from: &Code, to: &Code) -> Option<Route> {
+        let (times, previous) = self.dijkstra(from, None, Utc::now());
+        let (arrival, &minutes) = times
+            .iter()
+            .filter(|((code, _), _)| code == to)
+            .min_by_key(|(_, time)| **time)?;
+
+        // walk back from the destination to the start
+        let mut stations = vec![to.clone()];
+        let mut lines = vec![];
+        let mut current = arrival;
+        while let Some(stop) = previous.get(current) {
+            stations.push(stop.0.clone());
+            lines.extend(current.1.clone());
+            current = stop;
+        }
+        stations.reverse();
+        lines.reverse();
+
+        Some(Route {
+            stations,
+            lines,
+            minutes,
+        })
+    }
+
+    // A route as it would be announced, one leg per line, like "Tsuen Wan Line towards Central from Mong Kok to Admiralty".
+    pub fn directions(&self, route: &Route) -> Vec<String> {
+        let name = |code: &Code| self.stations.get(code).map_or(code.to_string(), |station| station.name.clone());
+
+        let mut directions = vec![];
+        let mut start = 0;
+        for (i, line_code) in route.lines.iter().enumerate() {
+            if route.lines.get(i + 1) == Some(line_code) {
+                continue; // still on the same line
+            }
+            let (from, to) = (&route.stations[start], &route.stations[i + 1]);
+            let leg = match self.lines.get(line_code) {
+                _ if *line_code == WALK => format!("Walk from {} to {}", name(from), name(to)),
+                Some(line) => match line.towards(from, to) {
+                    Some(terminus) => format!("{} towards {} from {} to {}", line.name, name(terminus), name(from), name(to)),
+                    None => format!("{} from {} to {}", line.name, name(from), name(to)),
+                },
+                None => format!("{} from {} to {}", line_code, name(from), name(to)),
+            };
+            directions.push(leg);
+            start = i + 1;
+        }
+        directions
+    }
+
+    // Every station that can be reached from a station within some minutes, with the fastest time to each, nearest first.
+    pub fn reachable_within(&self, from: &Code, minutes: usize) -> Vec<(Station, usize)> {
+        let (times, _) = self.dijkstra(from, Some(minutes), Utc::now());
+        let mut reachable: Vec<(Station, usize)> = Graph::fastest(times)
+            .into_iter()
+            .filter(|(code, _)| code != from)
+            .filter_map(|(code, time)| self.stations.get(&code).map(|station| (station.clone(), time)))
+            .collect();
+        reachable.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.code.cmp(&b.0.code)));
+        reachable
+    }
+
+    // Every station someone seen at one of some stations could be at by now, earliest first.
+    pub fn zone(&self, from: &[Code], minutes: usize) -> Zone {
+        // a time too far back to represent can't be a sighting this game, so just use the current service
+        let now = Utc::now();
+        let seen = i64::try_from(minutes)
+            .ok()
+            .and_then(chrono::Duration::try_minutes)
+            .and_then(|ago| now.checked_sub_signed(ago))
+            .unwrap_or(now);
+        let mut arrivals: HashMap<Code, usize> = HashMap::new();
+        for start in from {
+            let (times, _) = self.dijkstra(start, Some(minutes), seen);
+            for (code, time) in Graph::fastest(times) {
+                let arrival = arrivals.entry(code).or_insert(time);
+                *arrival = (*arrival).min(time);
+            }
+        }
+
+        let mut stations: Vec<ZoneStation> = arrivals
+            .into_iter()
+            .filter_map(|(code, arrival)| {
+                let station = self.stations.get(&code)?;
+                Some(ZoneStation {
+                    code,
+                    name: station.name.clone(),
+                    latitude: station.latitude,
+                    longitude: station.longitude,
+                    arrival,
+                })
+            })
+            .collect();
+        stations.sort_by(|a, b| a.arrival.cmp(&b.arrival).then_with(|| a.code.cmp(&b.code)));
+
+        Zone {
+            from: from.to_vec(),
+            minutes,
+            stations,
+        }
+    }
+
+    // The station closest to a location as the crow flies, and how many metres away it is.
+    // Nothing if there is no station within the maximum distance, when one is given.
+    pub fn nearest_station(&self, location: &Location, max_distance: Option<f64>) -> Option<(&Station, f64)> {
+        let nearest = match max_distance {
+            Some(max) => self.index.within(location, max).into_iter().find(|(code, _)| self.is_open(code)),
+            None => {
+                // look further out until there is an open station, closures are rare so this is usually the first
+                let mut k = 1;
+                loop {
+                    let nearest = self.index.nearest(location, k);
+                    if let Some(&found) = nearest.iter().find(|(code, _)| self.is_open(code)) {
+                        break Some(found);
+                    }
+                    if k >= self.index.len() {
+                        break None;
                     }
+                    k *= 2;
                 }
             }
+        };
+        nearest.and_then(|(code, distance)| Some((self.stations.get(code)?, distance)))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RouteQuery {
+    from: String,
+    to: String,
+}
+
+// travel time between two stations, e.g. /route?from=TSW&to=CEN
+pub async fn route(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Query(query): Query<RouteQuery>,
+) -> impl IntoResponse {
+    let (Ok(from), Ok(to)) = (Code::from_str(&query.from), Code::from_str(&query.to)) else {
+        return Json(json!({"error": "Invalid station code"}));
+    };
+
+    let game = game.read().await;
+    for code in [&from, &to] {
+        if !game.graph.stations.contains_key(code) {
+            return Json(json!({"error": format!("Station {} not found", code)}));
+        }
+        if !game.graph.is_open(code) {
+            return Json(json!({"error": format!("Station {} is closed", code)}));
+        }
+    }
+
+    match game.graph.shortest_path(&from, &to) {
+        Some(route) => {
+            let mut json = json!(route);
+            json["directions"] = json!(game.graph.directions(&route));
+            Json(json)
+        }
+        None => Json(json!({"error": "No route between these stations"})),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ZoneQuery {
+    station: String,
+    minutes: usize,
+}
+
+// seekers and admins only, where hiders last seen at a station could be after some minutes, e.g. /zone?station=MOK&minutes=20
+pub async fn zone(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Extension(username): Extension<String>,
+    Extension(admin): Extension<bool>,
+    Query(query): Query<ZoneQuery>,
+) -> impl IntoResponse {
+    let game = game.read().await;
+    match zone_for(&game, &username, admin, &query.station, query.minutes) {
+        Ok(zone) => Json(json!(zone)),
+        Err(error) => Json(json!({"error": error})),
+    }
+}
+
+// The same over the websocket, {"op": "zone", "station": "MOK", "minutes": 20}
+pub async fn handle_zone_op(json: &serde_json::Value, who: &str, admin: bool, game: &Arc<RwLock<Game>>) {
+    let station = json.get("station").and_then(|station| station.as_str()).unwrap_or_default();
+    let minutes = json.get("minutes").and_then(|minutes| minutes.as_u64()).unwrap_or_default();
+
+    let game = game.read().await;
+    let msg = match zone_for(&game, who, admin, station, minutes as usize) {
+        Ok(zone) => {
+            let mut msg = json!(zone);
+            msg["op"] = json!("zone");
+            msg
         }
-        // return the vector
-        result
+        Err(error) => json!({"op": "zone", "error": error}),
+    };
+    if let Some(player) = game.players.get(who) {
+        if let Err(e) = player.send_msg(Message::Text(msg.to_string())).await {
+            tracing::error!("Failed to send the zone to {}: {}", who, e);
+        }
+    }
+}
+
+// Admins can always look, as the roles of a round are only ever hider, seeker or spectator.
+fn zone_for(game: &Game, who: &str, admin: bool, station: &str, minutes: usize) -> Result<Zone, String> {
+    let player = game.get_player(who)?;
+    if !admin && !player.ptype.is_seeker() {
+        return Err("Only seekers can see where the hiders could be".to_string());
+    }
+    let code = Code::from_str(station).map_err(|_| "Invalid station code".to_string())?;
+    if !game.graph.stations.contains_key(&code) {
+        return Err(format!("Station {} not found", code));
+    }
+    // hiders can't have been seen longer ago than a whole round
+    let longest = ((game.settings.hide_duration + game.settings.seek_duration) / 60) as usize;
+    if minutes > longest {
+        return Err(format!("Minutes must be at most {}", longest));
+    }
+    Ok(game.graph.zone(&[code], minutes))
+}
+
+#[derive(Deserialize)]
+pub struct ReloadQuery {
+    path: Option<String>,
+}
+
+// admin only, swap in a fresh copy of the network between games, e.g. /network/reload?path=data/singapore.csv
+// without a path the configured network is read again.
+pub async fn reload(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Extension(username): Extension<String>,
+    Query(query): Query<ReloadQuery>,
+) -> impl IntoResponse {
+    let mut game = game.write().await;
+    if game.state.read().await.phase() != Phase::Lobby {
+        return Json(json!({"error": "The network can only be reloaded between games"}));
     }
+
+    let path = query.path.or_else(|| game.settings.network.clone());
+    let graph = match path.as_deref() {
+        Some(path) => match Graph::load(Path::new(path)) {
+            Ok(graph) => graph,
+            Err(error) => return Json(json!({"error": format!("Could not load the network: {}", error)})),
+        },
+        None => Graph::from_csv(),
+    };
+
+    let issues: Vec<String> = graph.validate().iter().map(|issue| issue.to_string()).collect();
+    if game.settings.strict_network && !issues.is_empty() {
+        return Json(json!({"error": "The network data has problems", "issues": issues}));
+    }
+
+    tracing::info!(
+        "admin {} reloaded the network from {}",
+        username,
+        path.as_deref().unwrap_or("the built in data")
+    );
+    let (stations, connections) = (graph.stations.len(), graph.connections.len());
+    let mut graph = graph;
+    graph.add_walks(&game.settings);
+    graph.mask = game.graph.mask.clone(); // closures last until the end of the game, whatever the data
+    game.graph = graph;
+    game.settings.network = path;
+    Json(json!({
+        "message": "Network reloaded",
+        "stations": stations,
+        "connections": connections,
+        "issues": issues,
+    }))
 }
 
 const CSV: &str = r#"
@@ -159,11 +920,14 @@ FOT,Fo Tan,22.3953371,114.198366,5,EAL
 SHT,Sha Tin,22.3826796,114.1877459,4,EAL
 TAW,Tai Wai,22.3728022,114.1787838,4,EAL
 KOT,Kowloon Tong,22.3369989,114.1758397,6,EAL
-MKK,Mong Kok East,22.3217126,22.3217126,4,EAL
+MKK,Mong Kok East,22.3217126,114.1722,4,EAL
 HUH,Hung Hom,22.3027415,114.182136,5,EAL
 EXC,Exhibition Centre,22.2816654,114.1757015,5,EAL
 ADM,Admiralty,22.2790893,114.1653075,3,EAL
 ,,,,,
+SHS,Sheung Shui,22.501124,114.1280498,-,EAL
+LMC,Lok Ma Chau,22.5149,114.0659,8,EAL
+,,,,,
 CHW,Chai Wan,22.2646033,114.2370517,-,ISL
 HFC,Heng Fa Chuen,22.276619,114.2397239,3,ISL
 SKW,Shau Kei Wan,22.2790272,114.2286373,3,ISL
@@ -197,7 +961,7 @@ SKM,Shek Kip Mei,22.3318072,114.1687327,3,KTL
 PRE,Prince Edward,22.3244648,114.1683614,3,KTL
 MOK,Mong Kok,22.319263,114.1694488,3,KTL
 YMT,Yau Ma Tei,22.3130692,114.1707484,3,KTL
-HOM,Ho Man Tin,22.3093727,22.3093727,4,KTL
+HOM,Ho Man Tin,22.3093727,114.1827,4,KTL
 WHA,Whampoa,22.304901,114.1896461,4,KTL
 ,,,,,
 TUM,Tuen Mun,22.3952837,113.9733214,-,TML
@@ -212,7 +976,7 @@ NAC,Nam Cheong,22.3266391,114.1538155,4,TML
 AUS,Austin,22.3041946,114.166541,4,TML
 ETS,East Tsim Sha Tsui,22.2952166,114.1746402,4,TML
 HUH,Hung Hom,22.3027415,114.182136,4,TML
-HOM,Ho Man Tin,22.3093727,22.3093727,4,TML
+HOM,Ho Man Tin,22.3093727,114.1827,4,TML
 TKW,To Kwa Wan,22.3169869,114.1875844,4,TML
 SUW,Sung Wong Toi,22.32578,114.1914117,4,TML
 KAT,Kai Tak,22.3303836,114.1992233,3,TML
@@ -226,7 +990,7 @@ SHM,Shek Mun,22.3877892,114.2085474,3,TML
 TSH,Tai Shui Hang,22.4084544,114.222844,5,TML
 HEO,Heng On,22.4178164,114.2259505,3,TML
 MOS,Ma On Shan,22.4249134,114.2317637,4,TML
-WKS,Wu Kai Sha,22.4292202,114.243854,,TML
+WKS,Wu Kai Sha,22.4292202,114.243854,3,TML
 ,,,,,
 TUC,Tung Chung,22.289278,113.9413221,-,TCL
 SUN,Sunny Bay,22.3318274,114.0288778,10,TCL
@@ -246,9 +1010,9 @@ QUB,Quarry Bay,22.2882652,114.2090338,5,TKL
 NOP,North Point,22.2904837,114.2005605,3,TKL
 ,,,,,
 TKO,Tseung Kwan O,22.3074422,114.2599465,-,TKL
-TKO,Lohas Park,22.2956555,114.2687716,7,TKL
+LHP,Lohas Park,22.2956555,114.2687716,7,TKL
 ,,,,,
-TSW,Tsuen Wan,22.3683642,114.1099384,-,TWL
+TSW,Tsuen Wan,22.3736,114.1178,-,TWL
 TWH,Tai Wo Hau,22.3708,114.125,3,TWL
 KWH,Kwai Hing,22.3632,114.1312,3,TWL
 KWF,Kwai Fong,22.3569,114.1279,3,TWL
@@ -286,6 +1050,7 @@ const STATIONS: &str = r#"
         latitude: 22.3263889,
         longitude: 114.1536861,
         line_code: "TCL",
+        lines: ["TML", "TCL"],
     ),
     "WHA": (
         code: "WHA",
@@ -302,6 +1067,7 @@ const STATIONS: &str = r#"
         latitude: 22.3040231,
         longitude: 114.252426,
         line_code: "TKL",
+        lines: ["KTL", "TKL"],
     ),
     "ETS": (
         code: "ETS",
@@ -310,6 +1076,7 @@ const STATIONS: &str = r#"
         latitude: 22.2952166,
         longitude: 114.1746402,
         line_code: "WLK",
+        lines: ["TML", "WLK"],
     ),
     "HIK": (
         code: "HIK",
@@ -326,6 +1093,7 @@ const STATIONS: &str = r#"
         latitude: 22.3396397,
         longitude: 114.2012733,
         line_code: "TML",
+        lines: ["KTL", "TML"],
     ),
     "WTS": (
         code: "WTS",
@@ -342,6 +1110,7 @@ const STATIONS: &str = r#"
         latitude: 22.282,
         longitude: 114.1576,
         line_code: "WLK",
+        lines: ["ISL", "TWL", "WLK"],
     ),
     "YUL": (
         code: "YUL",
@@ -358,6 +1127,7 @@ const STATIONS: &str = r#"
         latitude: 22.3388904,
         longitude: 114.136522,
         line_code: "TWL",
+        lines: ["TML", "TWL"],
     ),
     "STW": (
         code: "STW",
@@ -414,6 +1184,7 @@ const STATIONS: &str = r#"
         latitude: 22.3129,
         longitude: 114.1707,
         line_code: "TWL",
+        lines: ["KTL", "TWL"],
     ),
     "QUB": (
         code: "QUB",
@@ -422,6 +1193,7 @@ const STATIONS: &str = r#"
         latitude: 22.2882652,
         longitude: 114.2090338,
         line_code: "TKL",
+        lines: ["ISL", "TKL"],
     ),
     "LAT": (
         code: "LAT",
@@ -446,6 +1218,7 @@ const STATIONS: &str = r#"
         latitude: 22.2788,
         longitude: 114.1646,
         line_code: "SIL",
+        lines: ["EAL", "ISL", "TWL", "SIL"],
     ),
     "SUN": (
         code: "SUN",
@@ -454,6 +1227,7 @@ const STATIONS: &str = r#"
         latitude: 22.3318274,
         longitude: 114.0288778,
         line_code: "TCL",
+        lines: ["DRL", "TCL"],
     ),
     "SHS": (
         code: "SHS",
@@ -484,8 +1258,9 @@ const STATIONS: &str = r#"
         id: 0,
         name: "Ho Man Tin",
         latitude: 22.3093727,
-        longitude: 22.3093727,
+        longitude: 114.1827,
         line_code: "TML",
+        lines: ["KTL", "TML"],
     ),
     "SHW": (
         code: "SHW",
@@ -514,6 +1289,14 @@ const STATIONS: &str = r#"
     "TKO": (
         code: "TKO",
         id: 0,
+        name: "Tseung Kwan O",
+        latitude: 22.3074422,
+        longitude: 114.2599465,
+        line_code: "TKL",
+    ),
+    "LHP": (
+        code: "LHP",
+        id: 0,
         name: "Lohas Park",
         latitude: 22.2956555,
         longitude: 114.2687716,
@@ -526,6 +1309,7 @@ const STATIONS: &str = r#"
         latitude: 22.3728022,
         longitude: 114.1787838,
         line_code: "TML",
+        lines: ["EAL", "TML"],
     ),
     "HKU": (
         code: "HKU",
@@ -558,6 +1342,7 @@ const STATIONS: &str = r#"
         latitude: 22.3191,
         longitude: 114.1694,
         line_code: "TWL",
+        lines: ["KTL", "TWL"],
     ),
     "YAT": (
         code: "YAT",
@@ -566,6 +1351,7 @@ const STATIONS: &str = r#"
         latitude: 22.2977718,
         longitude: 114.2370927,
         line_code: "TKL",
+        lines: ["KTL", "TKL"],
     ),
     "CHH": (
         code: "CHH",
@@ -612,7 +1398,7 @@ const STATIONS: &str = r#"
         id: 0,
         name: "Mong Kok East",
         latitude: 22.3217126,
-        longitude: 22.3217126,
+        longitude: 114.1722,
         line_code: "EAL",
     ),
     "SKW": (
@@ -630,6 +1416,7 @@ const STATIONS: &str = r#"
         latitude: 22.3048965,
         longitude: 114.1616963,
         line_code: "TCL",
+        lines: ["AEL", "TCL"],
     ),
     "UNI": (
         code: "UNI",
@@ -662,6 +1449,7 @@ const STATIONS: &str = r#"
         latitude: 22.3484,
         longitude: 114.1261,
         line_code: "TWL",
+        lines: ["TCL", "TWL"],
     ),
     "OLY": (
         code: "OLY",
@@ -683,8 +1471,8 @@ const STATIONS: &str = r#"
         code: "TSW",
         id: 0,
         name: "Tsuen Wan",
-        latitude: 22.3683642,
-        longitude: 114.1099384,
+        latitude: 22.3736,
+        longitude: 114.1178,
         line_code: "TWL",
     ),
     "TSY": (
@@ -694,6 +1482,7 @@ const STATIONS: &str = r#"
         latitude: 22.3583861,
         longitude: 114.1074312,
         line_code: "TCL",
+        lines: ["AEL", "TCL"],
     ),
     "FAN": (
         code: "FAN",
@@ -726,6 +1515,7 @@ const STATIONS: &str = r#"
         latitude: 22.3027415,
         longitude: 114.182136,
         line_code: "TML",
+        lines: ["EAL", "TML"],
     ),
     "SSP": (
         code: "SSP",
@@ -750,6 +1540,7 @@ const STATIONS: &str = r#"
         latitude: 22.2973,
         longitude: 114.1722,
         line_code: "WLK",
+        lines: ["TWL", "WLK"],
     ),
     "WAC": (
         code: "WAC",
@@ -822,6 +1613,7 @@ const STATIONS: &str = r#"
         latitude: 22.336867,
         longitude: 114.177541,
         line_code: "KTL",
+        lines: ["EAL", "KTL"],
     ),
     "KSR": (
         code: "KSR",
@@ -910,6 +1702,7 @@ const STATIONS: &str = r#"
         latitude: 22.2851469,
         longitude: 114.1584546,
         line_code: "WLK",
+        lines: ["AEL", "TCL", "WLK"],
     ),
     "SYP": (
         code: "SYP",
@@ -942,6 +1735,7 @@ const STATIONS: &str = r#"
         latitude: 22.3245,
         longitude: 114.1683,
         line_code: "TWL",
+        lines: ["KTL", "TWL"],
     ),
     "SHM": (
         code: "SHM",
@@ -998,6 +1792,7 @@ const STATIONS: &str = r#"
         latitude: 22.2904837,
         longitude: 114.2005605,
         line_code: "TKL",
+        lines: ["ISL", "TKL"],
     ),
     "AUS": (
         code: "AUS",
@@ -1060,7 +1855,6 @@ const CONNECTIONS: &str = r#"
     ("HAH", "TKO", 4),
     ("CEN", "HOK", 8),
     ("KAT", "DIH", 4),
-    ("HOK", "TST", 0),
     ("TAW", "CKT", 3),
     ("STW", "CIO", 4),
     ("SKW", "SWH", 3),
@@ -1112,7 +1906,7 @@ const CONNECTIONS: &str = r#"
     ("NOP", "FOH", 3),
     ("SUN", "TSY", 10),
     ("AWE", "AIR", 2),
-    ("TKO", "TKO", 7),
+    ("TKO", "LHP", 7),
     ("AIR", "TSY", 14),
     ("TIK", "YAT", 4),
     ("KWT", "NTK", 3),
@@ -1125,7 +1919,7 @@ const CONNECTIONS: &str = r#"
     ("SHT", "TAW", 4),
     ("SUN", "DIS", 6),
     ("SHM", "TSH", 5),
-    ("MOS", "WKS", 0),
+    ("MOS", "WKS", 3),
     ("KOT", "SKM", 3),
     ("NAC", "OLY", 4),
     ("LET", "WCH", 4),
@@ -1146,3 +1940,408 @@ const CONNECTIONS: &str = r#"
     ("SOH", "LET", 4),
 ]
 "#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line::Headway;
+    use crate::player::PlayerType;
+    use crate::settings::Settings;
+
+    fn code(code: &str) -> Code {
+        Code::from_str(code).unwrap()
+    }
+
+    // trains that are always waiting, so the times only depend on the network
+    fn graph(csv: &str) -> Graph {
+        let mut graph = Graph::parse_csv(csv).unwrap();
+        for line in graph.lines.values_mut() {
+            line.headway = Headway {
+                peak: 0,
+                off_peak: 0,
+                late_night: 0,
+            };
+        }
+        graph
+    }
+
+    // A to D is quick through C, but B comes first and leads to D slowly
+    const BRANCHES: &str = "A,Alpha,22.30,114.10,-,ONE
+B,Bravo,22.31,114.10,1,ONE
+D,Delta,22.33,114.10,20,ONE
+,,,,,
+A,Alpha,22.30,114.10,-,ONE
+C,Charlie,22.32,114.11,5,ONE
+D,Delta,22.33,114.10,5,ONE";
+
+    // X to Z directly, or quicker changing lines at Y if changing there is quick enough
+    const CHANGE: &str = "X,X-ray,22.30,114.10,-,ONE
+Y,Yankee,22.31,114.10,5,ONE
+,,,,,
+Y,Yankee,22.31,114.10,-,TWO
+Z,Zulu,22.32,114.10,5,TWO
+,,,,,
+X,X-ray,22.30,114.10,-,SLO
+Z,Zulu,22.32,114.10,12,SLO";
+
+    fn times(reachable: Vec<(Station, usize)>) -> Vec<(Code, usize)> {
+        reachable.into_iter().map(|(station, time)| (station.code, time)).collect()
+    }
+
+    #[test]
+    fn slow_branch_is_relaxed_by_a_faster_one() {
+        let graph = graph(BRANCHES);
+        let route = graph.shortest_path(&code("A"), &code("D")).unwrap();
+        assert_eq!(route.minutes, 10);
+        assert_eq!(route.stations, vec![code("A"), code("C"), code("D")]);
+        assert_eq!(route.lines, vec![code("ONE"), code("ONE")]);
+
+        let reachable = times(graph.reachable_within(&code("A"), 60));
+        assert_eq!(reachable, vec![(code("B"), 1), (code("C"), 5), (code("D"), 10)]);
+    }
+
+    #[test]
+    fn changing_lines_costs_the_transfer_time() {
+        let mut graph = graph(CHANGE);
+        graph.transfers = Transfers {
+            default: 3,
+            stations: HashMap::new(),
+        };
+        // 5 + 3 + 5 is slower than staying on the slow line
+        let route = graph.shortest_path(&code("X"), &code("Z")).unwrap();
+        assert_eq!(route.minutes, 12);
+        assert_eq!(route.lines, vec![code("SLO")]);
+
+        graph.transfers.stations.insert(code("Y"), 1);
+        let route = graph.shortest_path(&code("X"), &code("Z")).unwrap();
+        assert_eq!(route.minutes, 11);
+        assert_eq!(route.stations, vec![code("X"), code("Y"), code("Z")]);
+        assert_eq!(route.lines, vec![code("ONE"), code("TWO")]);
+    }
+
+    #[test]
+    fn nothing_past_the_limit() {
+        let graph = graph(BRANCHES);
+        assert_eq!(times(graph.reachable_within(&code("A"), 9)), vec![(code("B"), 1), (code("C"), 5)]);
+        assert_eq!(times(graph.reachable_within(&code("A"), 10)).last(), Some(&(code("D"), 10)));
+        assert!(graph.reachable_within(&code("A"), 0).is_empty());
+    }
+
+    #[test]
+    fn unknown_and_unreachable_stations() {
+        let graph = graph(BRANCHES);
+        assert!(graph.shortest_path(&code("A"), &code("Q")).is_none());
+        assert!(graph.shortest_path(&code("Q"), &code("A")).is_none());
+        assert!(graph.reachable_within(&code("Q"), 60).is_empty());
+
+        let route = graph.shortest_path(&code("A"), &code("A")).unwrap();
+        assert_eq!(route.minutes, 0);
+        assert_eq!(route.stations, vec![code("A")]);
+    }
+
+    // the line a connection in the data is on
+    fn line_between(graph: &Graph, a: &str, b: &str) -> Option<Code> {
+        graph
+            .connections
+            .iter()
+            .find(|connection| **connection == code(a) && **connection == code(b))
+            .map(|connection| connection.3.clone())
+    }
+
+    #[test]
+    fn legacy_ron_connections_are_on_a_line_both_stations_are_on() {
+        let mut graph = Graph::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../util"))).unwrap();
+        for Connection(from, to, _, line) in graph.connections.iter() {
+            assert!(graph.stations[from].is_on_line(line), "{} to {} on {}", from, to, line);
+            assert!(graph.stations[to].is_on_line(line), "{} to {} on {}", from, to, line);
+        }
+        assert_eq!(line_between(&graph, "ADM", "CEN"), Some(code("ISL")));
+        assert_eq!(line_between(&graph, "TST", "ADM"), Some(code("TWL")));
+        assert_eq!(line_between(&graph, "JOR", "TST"), Some(code("TWL")));
+        assert_eq!(line_between(&graph, "AIR", "TSY"), Some(code("AEL")));
+        assert_eq!(line_between(&graph, "TSY", "KOW"), Some(code("AEL")));
+        assert_eq!(line_between(&graph, "TSY", "LAK"), Some(code("TCL")));
+        // the only walks in the data
+        assert_eq!(line_between(&graph, "CEN", "HOK"), Some(Code::walk()));
+        assert_eq!(line_between(&graph, "TST", "ETS"), Some(Code::walk()));
+        assert_eq!(graph.connections.iter().filter(|connection| connection.3 == WALK).count(), 2);
+        assert!(graph.load_issues.is_empty());
+
+        graph.walking = false;
+        let route = graph.shortest_path(&code("TSW"), &code("CEN")).unwrap();
+        assert!(!route.lines.contains(&Code::walk()));
+    }
+
+    #[test]
+    fn legacy_ron_connections_without_a_common_line_are_left_out() {
+        let stations = r#"{
+            "AAA": (code: "AAA", name: "Alpha", latitude: 22.30, longitude: 114.10, line_code: "ONE"),
+            "BBB": (code: "BBB", name: "Bravo", latitude: 22.31, longitude: 114.10, line_code: "ONE", lines: ["ONE", "TWO"]),
+            "CCC": (code: "CCC", name: "Charlie", latitude: 22.32, longitude: 114.10, line_code: "TWO"),
+        }"#;
+        let connections = r#"[("AAA", "BBB", 2), ("BBB", "CCC", 3), ("AAA", "CCC", 4)]"#;
+        let graph = Graph::parse_ron(stations, connections).unwrap();
+        assert_eq!(line_between(&graph, "AAA", "BBB"), Some(code("ONE")));
+        assert_eq!(line_between(&graph, "BBB", "CCC"), Some(code("TWO")));
+        assert_eq!(line_between(&graph, "AAA", "CCC"), None);
+        assert!(matches!(
+            graph.validate().as_slice(),
+            [Issue::NoCommonLine { from, to }] if *from == code("AAA") && *to == code("CCC")
+        ));
+    }
+
+    // noon on a Monday in Hong Kong, off peak
+    fn noon() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-01T12:00:00+08:00").unwrap().to_utc()
+    }
+
+    // trains every 4 minutes off peak on every line, or as often as given
+    fn timetabled(csv: &str, headways: &[(&str, usize)]) -> Graph {
+        let mut graph = graph(csv);
+        graph.transfers = Transfers {
+            default: 0,
+            stations: HashMap::new(),
+        };
+        for (code, line) in graph.lines.iter_mut() {
+            let off_peak = headways.iter().find(|(line, _)| code.0 == *line).map_or(4, |(_, minutes)| *minutes);
+            line.headway = Headway {
+                peak: off_peak,
+                off_peak,
+                late_night: 2 * off_peak,
+            };
+        }
+        graph
+    }
+
+    fn fastest_from(graph: &Graph, start: &str, departure: DateTime<Utc>) -> HashMap<Code, usize> {
+        Graph::fastest(graph.dijkstra(&code(start), None, departure).0)
+    }
+
+    #[test]
+    fn boarding_waits_for_a_train_but_staying_on_it_doesnt() {
+        let graph = timetabled(BRANCHES, &[]);
+        let times = fastest_from(&graph, "A", noon());
+        // half the 4 minutes between trains, once, however far the train goes
+        assert_eq!(times[&code("B")], 2 + 1);
+        assert_eq!(times[&code("D")], 2 + 5 + 5);
+
+        // trains only every 8 minutes late at night
+        let late = noon() + chrono::Duration::hours(11);
+        assert_eq!(fastest_from(&graph, "A", late)[&code("D")], 4 + 5 + 5);
+    }
+
+    #[test]
+    fn changing_lines_waits_for_the_next_train() {
+        // without waiting, changing at Y takes 10 minutes against 12 on the slow line
+        let graph = timetabled(CHANGE, &[("TWO", 10)]);
+        let times = fastest_from(&graph, "X", noon());
+        assert_eq!(times[&code("Y")], 2 + 5);
+        // 2 + 5 then 5 for a train on TWO and 5 more is slower than 2 + 12 on the slow line
+        assert_eq!(times[&code("Z")], 2 + 12);
+    }
+
+    #[test]
+    fn walking_doesnt_wait() {
+        let csv = "P,Papa,22.30,114.10,-,ONE
+Q,Quebec,22.31,114.10,4,ONE
+,,,,,
+Q,Quebec,22.31,114.10,-,WLK
+R,Romeo,22.312,114.10,3,WLK";
+        let graph = timetabled(csv, &[]);
+        assert_eq!(fastest_from(&graph, "R", noon())[&code("Q")], 3);
+        assert_eq!(fastest_from(&graph, "P", noon())[&code("R")], 2 + 4 + 3);
+        // and back onto the train after the walk
+        assert_eq!(fastest_from(&graph, "R", noon())[&code("P")], 3 + 2 + 4);
+    }
+
+    // P and Q are 445 metres apart on lines that don't meet, R and T are linked by a walk in the data
+    const WALKS: &str = "P,Papa,22.300,114.10,-,ONE
+S,Sierra,22.290,114.10,3,ONE
+,,,,,
+Q,Quebec,22.304,114.10,-,TWO
+R,Romeo,22.320,114.10,4,TWO
+,,,,,
+R,Romeo,22.320,114.10,-,WLK
+T,Tango,22.322,114.10,3,WLK";
+
+    #[test]
+    fn walks_link_stations_within_the_walking_distance() {
+        let mut graph = graph(WALKS);
+        graph.add_walks(&Settings::default());
+        // only the one pair, the others are too far apart or already linked
+        assert_eq!(graph.walks.len(), 1);
+        let walk = graph.walks.iter().next().unwrap();
+        assert!(*walk == code("P") && *walk == code("Q"));
+        // 445 metres at 80 a minute
+        assert_eq!(walk.2, 6);
+        assert_eq!(graph.shortest_path(&code("P"), &code("Q")).unwrap().lines, vec![Code::walk()]);
+
+        graph.add_walks(&Settings {
+            walk_distance: 400.0,
+            ..Settings::default()
+        });
+        assert!(graph.walks.is_empty());
+        assert!(graph.shortest_path(&code("P"), &code("Q")).is_none());
+    }
+
+    #[test]
+    fn turning_walking_off_leaves_out_every_walk() {
+        let mut graph = graph(WALKS);
+        graph.add_walks(&Settings {
+            walking: false,
+            ..Settings::default()
+        });
+        assert!(graph.shortest_path(&code("P"), &code("Q")).is_none());
+        assert!(graph.shortest_path(&code("R"), &code("T")).is_none());
+        assert!(graph.neighbours(&code("R")).iter().all(|(_, _, line)| *line != WALK));
+
+        graph.add_walks(&Settings::default());
+        assert_eq!(graph.shortest_path(&code("R"), &code("T")).unwrap().minutes, 3);
+    }
+
+    #[test]
+    fn no_walk_between_tsuen_wan_and_tsuen_wan_west() {
+        let mut graph = Graph::from_csv();
+        graph.add_walks(&Settings::default());
+        assert!(!graph.walks.iter().any(|walk| *walk == code("TSW") && *walk == code("TWW")));
+    }
+
+    #[tokio::test]
+    async fn only_seekers_and_admins_see_the_zone() {
+        let (mut game, _) = Game::offline(&["seeker", "watcher"]).await;
+        game.players.get_mut("seeker").unwrap().ptype = PlayerType::PrimarySeeker;
+        assert!(zone_for(&game, "seeker", false, "MOK", 10).is_ok());
+        assert!(zone_for(&game, "watcher", false, "MOK", 10).is_err());
+        // whatever their role this round
+        assert!(zone_for(&game, "watcher", true, "MOK", 10).is_ok());
+        assert!(zone_for(&game, "nobody", true, "MOK", 10).is_err());
+    }
+
+    fn zone_times(zone: &Zone) -> Vec<(Code, usize)> {
+        zone.stations.iter().map(|station| (station.code.clone(), station.arrival)).collect()
+    }
+
+    #[test]
+    fn the_zone_is_everywhere_reachable_in_the_minutes_since_the_sighting() {
+        let graph = graph(BRANCHES);
+        let zone = graph.zone(&[code("A")], 5);
+        assert_eq!(zone_times(&zone), vec![(code("A"), 0), (code("B"), 1), (code("C"), 5)]);
+        assert_eq!(zone_times(&graph.zone(&[code("A")], 10)).last(), Some(&(code("D"), 10)));
+
+        // seen at more than one station, the earliest anyone could be anywhere
+        let zone = graph.zone(&[code("A"), code("D")], 4);
+        assert_eq!(zone_times(&zone), vec![(code("A"), 0), (code("D"), 0), (code("B"), 1)]);
+
+        // far too long ago to be a date doesn't panic
+        assert_eq!(graph.zone(&[code("A")], usize::MAX).stations.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn zones_are_at_most_a_round_long() {
+        let (mut game, _) = Game::offline(&["seeker"]).await;
+        game.players.get_mut("seeker").unwrap().ptype = PlayerType::PrimarySeeker;
+        // 15 minutes hiding and 60 seeking
+        assert!(zone_for(&game, "seeker", false, "MOK", 75).is_ok());
+        assert_eq!(
+            zone_for(&game, "seeker", false, "MOK", 76).map(|zone| zone.minutes),
+            Err("Minutes must be at most 75".to_string())
+        );
+        assert!(zone_for(&game, "seeker", false, "MOK", usize::MAX).is_err());
+        assert!(zone_for(&game, "seeker", false, "XYZ", 10).is_err());
+    }
+
+    fn nearest(graph: &Graph, latitude: f64, longitude: f64, max_distance: Option<f64>) -> Option<(Code, f64)> {
+        graph
+            .nearest_station(&Location::new(latitude, longitude), max_distance)
+            .map(|(station, distance)| (station.code.clone(), distance))
+    }
+
+    #[test]
+    fn the_nearest_station_in_the_city() {
+        let mut graph = Graph::from_csv();
+        // outside exit A of Central, then on the harbourfront by Hong Kong station
+        let (station, distance) = nearest(&graph, 22.2818, 114.1580, None).unwrap();
+        assert_eq!(station, code("CEN"));
+        assert!(distance < 50.0);
+        assert_eq!(nearest(&graph, 22.2856, 114.1582, None).unwrap().0, code("HOK"));
+        assert_eq!(nearest(&graph, 22.2790, 114.1652, Some(100.0)).unwrap().0, code("ADM"));
+
+        // out in the harbour nothing is close, but there is always a nearest one
+        assert!(nearest(&graph, 22.2900, 114.1700, Some(500.0)).is_none());
+        assert!(nearest(&graph, 22.2900, 114.1700, None).is_some());
+
+        // a closed station is passed over for the next one along
+        graph.mask.stations.insert(code("CEN"));
+        let (station, further) = nearest(&graph, 22.2818, 114.1580, None).unwrap();
+        assert_ne!(station, code("CEN"));
+        assert!(further > distance);
+        assert!(nearest(&graph, 22.2818, 114.1580, Some(50.0)).is_none());
+    }
+
+    // a made up network somewhere else, codes of any length
+    const ELSEWHERE: &str = r#"(
+        projection: (latitude: 35.68, longitude: 139.76, x: 500, y: 400, x_per_degree: 1000.0, y_per_degree: -1000.0),
+        bounds: (latitude: (35.5, 35.9), longitude: (139.5, 140.0)),
+        utc_offset: 9,
+        stations: [
+            (code: "A", name: "Alpha", latitude: 35.68, longitude: 139.76),
+            (code: "BRAVO12", name: "Bravo", latitude: 35.69, longitude: 139.77),
+            (code: "C3", name: "Charlie", latitude: 35.70, longitude: 139.78),
+        ],
+        connections: [
+            ("A", "BRAVO12", 3, "LINE1"),
+            ("BRAVO12", "C3", 4, "L"),
+        ],
+    )"#;
+
+    #[test]
+    fn network_files_have_their_own_projection_bounds_and_time_zone() {
+        let graph = Graph::parse_network(ELSEWHERE).unwrap();
+        assert_eq!(graph.timezone.local_minus_utc(), 9 * 3600);
+        assert_eq!(graph.projection.project(35.68, 139.76), (500, 400));
+        assert_eq!(graph.projection.project(35.93, 139.51), (250, 150));
+        assert!(graph.bounds.contains(35.70, 139.78));
+        assert!(!graph.bounds.contains(22.28, 114.16));
+        assert!(graph.validate().is_empty());
+
+        // left out it is Hong Kong time
+        let graph = Graph::parse_network(&ELSEWHERE.replace("utc_offset: 9,", "")).unwrap();
+        assert_eq!(graph.timezone.local_minus_utc(), 8 * 3600);
+        assert_eq!(
+            Graph::parse_network(&ELSEWHERE.replace("utc_offset: 9,", "utc_offset: 30,")).err(),
+            Some("invalid utc offset 30".to_string())
+        );
+
+        // the bounds come from the file, not Hong Kong
+        let issues = Graph::parse_network(&ELSEWHERE.replace("latitude: 35.70,", "latitude: 22.30,"))
+            .unwrap()
+            .validate();
+        assert!(matches!(issues.as_slice(), [Issue::OutOfBounds { code: c, .. }] if *c == code("C3")));
+    }
+
+    #[test]
+    fn codes_can_be_any_length() {
+        let graph = Graph::parse_network(ELSEWHERE).unwrap();
+        let path = graph.shortest_path(&code("A"), &code("C3")).unwrap();
+        assert_eq!(path.stations, vec![code("A"), code("BRAVO12"), code("C3")]);
+        assert_eq!(path.lines, vec![code("LINE1"), code("L")]);
+        assert_eq!(graph.lines[&code("LINE1")].stations, vec![code("A"), code("BRAVO12")]);
+        assert_eq!(graph.stations[&code("BRAVO12")].line_codes(), vec![code("LINE1"), code("L")]);
+    }
+
+    #[test]
+    fn the_singapore_sample_loads() {
+        let graph = Graph::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../util/singapore.ron"))).unwrap();
+        assert!(graph.validate().is_empty());
+        assert_eq!(graph.timezone.local_minus_utc(), 8 * 3600);
+        assert_eq!(graph.stations[&code("NS25")].name, "City Hall");
+        assert_eq!(graph.projection.project(1.2931, 103.8520), (1000, 1000));
+        assert!(graph.stations.values().all(|station| graph.bounds.contains(station.latitude, station.longitude)));
+        assert!(!graph.bounds.contains(22.28, 114.16));
+        // three and four character codes on lines that meet at an interchange
+        let path = graph.shortest_path(&code("EW8"), &code("NS17")).unwrap();
+        assert_eq!(path.stations.first(), Some(&code("EW8")));
+        assert_eq!(path.stations.last(), Some(&code("NS17")));
+        assert_eq!(graph.lines[&code("NSL")].name, "North South Line");
+    }
+}
@@ -0,0 +1,22 @@
+pub mod auth;
+pub mod db;
+pub mod disruption;
+pub mod export;
+pub mod fare;
+pub mod game;
+pub mod line;
+pub mod location;
+pub mod logging;
+pub mod player;
+pub mod router;
+pub mod socket;
+pub mod teams;
+pub mod station;
+pub mod graph;
+pub mod states;
+pub mod state_machine;
+pub mod scores;
+pub mod settings;
+pub mod spatial;
+pub mod timer;
+pub mod validation;
@@ -0,0 +1,445 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use axum::{response::IntoResponse, Extension, Json};
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::{
+    game::Game,
+    station::{Code, Connection, WALK},
+};
+
+// A line of the network, with its stations in order from one end to the other.
+#[derive(Debug, Clone, Serialize)]
+pub struct Line {
+    pub code: Code,
+    pub name: String,
+    pub colour: String,
+    pub headway: Headway,
+    pub stations: Vec<Code>,      // the main line, end to end
+    pub branches: Vec<Vec<Code>>, // spurs off the main line, each from the station it leaves at to its end
+}
+
+// Name and colour of a line, as given in a network file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LineInfo {
+    pub code: Code,
+    pub name: String,
+    pub colour: String,
+    #[serde(default)]
+    pub headway: Option<Headway>,
+}
+
+// Minutes between trains at different times of day.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Headway {
+    pub peak: usize,       // weekdays 7:30 to 9:30 and 17:30 to 19:30
+    pub off_peak: usize,
+    pub late_night: usize, // 23:00 until the first trains
+}
+
+// for lines we know nothing about
+impl Default for Headway {
+    fn default() -> Self {
+        Headway {
+            peak: 4,
+            off_peak: 6,
+            late_night: 10,
+        }
+    }
+}
+
+impl Headway {
+    // Minutes between trains at a local time.
+    pub fn at(&self, time: DateTime<FixedOffset>) -> usize {
+        let minute = time.hour() * 60 + time.minute();
+        let weekday = !matches!(time.weekday(), Weekday::Sat | Weekday::Sun);
+        let rush = (7 * 60 + 30..9 * 60 + 30).contains(&minute) || (17 * 60 + 30..19 * 60 + 30).contains(&minute);
+
+        if !(6 * 60..23 * 60).contains(&minute) {
+            self.late_night
+        } else if weekday && rush {
+            self.peak
+        } else {
+            self.off_peak
+        }
+    }
+
+    // On average you arrive on the platform halfway between two trains.
+    pub fn wait(&self, time: DateTime<FixedOffset>) -> usize {
+        self.at(time).div_ceil(2)
+    }
+}
+
+// Official names, colours and rough headways of the MTR lines, for data that doesn't come with its own.
+fn mtr(code: &Code) -> Option<(&'static str, &'static str, Headway)> {
+    let headway = |peak, off_peak, late_night| Headway {
+        peak,
+        off_peak,
+        late_night,
+    };
+    Some(match code.0.as_str() {
+        "AEL" => ("Airport Express", "#00888A", headway(10, 10, 12)),
+        "DRL" => ("Disneyland Resort Line", "#F550A6", headway(4, 8, 10)),
+        "EAL" => ("East Rail Line", "#53B7E8", headway(3, 5, 8)),
+        "ISL" => ("Island Line", "#007DC5", headway(2, 4, 6)),
+        "KTL" => ("Kwun Tong Line", "#00AB4E", headway(2, 4, 6)),
+        "SIL" => ("South Island Line", "#BAC429", headway(3, 4, 6)),
+        "TCL" => ("Tung Chung Line", "#F38B00", headway(4, 7, 10)),
+        "TKL" => ("Tseung Kwan O Line", "#7D499D", headway(3, 4, 8)),
+        "TML" => ("Tuen Ma Line", "#923011", headway(3, 5, 8)),
+        "TWL" => ("Tsuen Wan Line", "#ED1D24", headway(2, 4, 6)),
+        _ => return None,
+    })
+}
+
+// What to call a line, its colour and how often it runs.
+fn describe(code: &Code) -> (String, String, Headway) {
+    match mtr(code) {
+        Some((name, colour, headway)) => (name.to_string(), colour.to_string(), headway),
+        None => (code.to_string(), "#888888".to_string(), Headway::default()),
+    }
+}
+
+impl Line {
+    // Work out the order of every line from the connections, the longest run between two ends is the main line
+    // and every other end is a branch off it. Walks between stations aren't a line.
+    pub fn from_connections(connections: &HashSet<Connection>) -> HashMap<Code, Line> {
+        let mut adjacent: HashMap<Code, HashMap<Code, Vec<Code>>> = HashMap::new();
+        for Connection(a, b, _, line) in connections.iter().filter(|connection| connection.3 != WALK) {
+            let stations = adjacent.entry(line.clone()).or_default();
+            stations.entry(a.clone()).or_default().push(b.clone());
+            stations.entry(b.clone()).or_default().push(a.clone());
+        }
+
+        adjacent
+            .into_iter()
+            .map(|(code, stations)| {
+                let (name, colour, headway) = describe(&code);
+                let (main, branches) = order(&stations);
+                let line = Line {
+                    code: code.clone(),
+                    name,
+                    colour,
+                    headway,
+                    stations: main,
+                    branches,
+                };
+                (code, line)
+            })
+            .collect()
+    }
+
+    // Lines as they are listed in the csv data, the first stretch of a line is the main line
+    // and any later ones are branches, which start at the station they leave from. Walks are left out.
+    pub fn from_segments(segments: Vec<(Code, Vec<Code>)>) -> HashMap<Code, Line> {
+        let mut lines: HashMap<Code, Line> = HashMap::new();
+        for (code, stations) in segments.into_iter().filter(|(code, _)| *code != WALK) {
+            match lines.get_mut(&code) {
+                Some(line) => line.branches.push(stations),
+                None => {
+                    let (name, colour, headway) = describe(&code);
+                    let line = Line {
+                        code: code.clone(),
+                        name,
+                        colour,
+                        headway,
+                        stations,
+                        branches: vec![],
+                    };
+                    lines.insert(code, line);
+                }
+            }
+        }
+        lines
+    }
+
+    // Both ends of the main line and the end of every branch.
+    pub fn termini(&self) -> Vec<&Code> {
+        let mut termini: Vec<&Code> = self.stations.first().into_iter().chain(self.stations.last()).collect();
+        termini.extend(self.branches.iter().filter_map(|branch| branch.last()));
+        termini.dedup();
+        termini
+    }
+
+    // Every way through the line from one end to another, along the main line or onto a branch.
+    fn runs(&self) -> Vec<Vec<&Code>> {
+        let mut runs = vec![self.stations.iter().collect::<Vec<_>>()];
+        for branch in self.branches.iter() {
+            let Some(junction) = branch.first().and_then(|first| self.stations.iter().position(|code| code == first))
+            else {
+                continue;
+            };
+            let spur = branch.iter().skip(1);
+            runs.push(self.stations[..=junction].iter().chain(spur.clone()).collect());
+            runs.push(self.stations[junction..].iter().rev().chain(spur).collect());
+        }
+        runs
+    }
+
+    // The end of the line a train from one station to another is heading for.
+    pub fn towards(&self, from: &Code, to: &Code) -> Option<&Code> {
+        self.runs().into_iter().find_map(|run| {
+            let start = run.iter().position(|code| *code == from)?;
+            let end = run.iter().position(|code| *code == to)?;
+            if start < end {
+                run.last().copied()
+            } else {
+                run.first().copied()
+            }
+        })
+    }
+}
+
+// The main line and branches of a single line, from which stations are next to which.
+fn order(adjacent: &HashMap<Code, Vec<Code>>) -> (Vec<Code>, Vec<Vec<Code>>) {
+    let mut ends: Vec<&Code> = adjacent
+        .iter()
+        .filter(|(_, next)| next.len() == 1)
+        .map(|(code, _)| code)
+        .collect();
+    ends.sort();
+
+    // a loop has no ends, so go round it from any station
+    let Some(first) = ends.first() else {
+        let start = adjacent.keys().min().cloned();
+        return (start.map(|start| walk(adjacent, start)).unwrap_or_default(), vec![]);
+    };
+
+    let mut main = vec![(*first).clone()];
+    for (i, a) in ends.iter().enumerate() {
+        for b in ends.iter().skip(i + 1) {
+            let run = path(adjacent, a, |code| code == *b);
+            if run.len() > main.len() {
+                main = run;
+            }
+        }
+    }
+
+    let mut covered: HashSet<Code> = main.iter().cloned().collect();
+    let mut branches = vec![];
+    for end in ends.iter() {
+        if covered.contains(*end) {
+            continue;
+        }
+        let mut branch = path(adjacent, end, |code| covered.contains(code));
+        if branch.last().is_some_and(|code| covered.contains(code)) {
+            branch.reverse(); // from the junction out to the end
+        } else {
+            // a stretch of line that doesn't join the rest, so take it end to end
+            branch = path(adjacent, end, |code| ends.contains(&code));
+        }
+        covered.extend(branch.iter().cloned());
+        branches.push(branch);
+    }
+    (main, branches)
+}
+
+// Shortest path from a station to the nearest station matching, or just the station if none can be reached.
+fn path(adjacent: &HashMap<Code, Vec<Code>>, start: &Code, target: impl Fn(&Code) -> bool) -> Vec<Code> {
+    let mut previous: HashMap<&Code, &Code> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    let mut seen = HashSet::from([start]);
+    while let Some(code) = queue.pop_front() {
+        if code != start && target(code) {
+            let mut path = vec![code.clone()];
+            let mut current = code;
+            while let Some(prev) = previous.get(current) {
+                path.push((*prev).clone());
+                current = prev;
+            }
+            path.reverse();
+            return path;
+        }
+        for next in adjacent.get(code).into_iter().flatten() {
+            if seen.insert(next) {
+                previous.insert(next, code);
+                queue.push_back(next);
+            }
+        }
+    }
+    vec![start.clone()]
+}
+
+// Follow a loop round from a station until it comes back.
+fn walk(adjacent: &HashMap<Code, Vec<Code>>, start: Code) -> Vec<Code> {
+    let mut stations = vec![start];
+    loop {
+        let current = stations.last().unwrap();
+        let next = adjacent
+            .get(current)
+            .into_iter()
+            .flatten()
+            .filter(|next| !stations.contains(next))
+            .min();
+        match next {
+            Some(next) => stations.push(next.clone()),
+            None => return stations,
+        }
+    }
+}
+
+// every line of the network, in order of their codes
+pub async fn get(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    let game = game.read().await;
+    let mut lines: Vec<&Line> = game.graph.lines.values().collect();
+    lines.sort_by(|a, b| a.code.cmp(&b.code));
+    let lines: Vec<_> = lines
+        .into_iter()
+        .map(|line| {
+            let mut json = json!(line);
+            json["termini"] = json!(line.termini());
+            json
+        })
+        .collect();
+    Json(json!(lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::graph::Graph;
+
+    fn code(code: &str) -> Code {
+        Code::from_str(code).unwrap()
+    }
+
+    fn codes(codes: &[&str]) -> Vec<Code> {
+        codes.iter().map(|c| code(c)).collect()
+    }
+
+    fn line(code_of_line: &str, pairs: &[(&str, &str)]) -> Line {
+        let connections: HashSet<Connection> = pairs
+            .iter()
+            .map(|(a, b)| Connection(code(a), code(b), 2, code(code_of_line)))
+            .collect();
+        Line::from_connections(&connections).remove(&code(code_of_line)).unwrap()
+    }
+
+    // Tseung Kwan O Line from Po Lam to Yau Tong, with the Lohas Park spur off Tseung Kwan O
+    fn tseung_kwan_o() -> Line {
+        line(
+            "TKL",
+            &[("POA", "HAH"), ("HAH", "TKO"), ("TKO", "TIK"), ("TIK", "YAT"), ("TKO", "LHP")],
+        )
+    }
+
+    #[test]
+    fn longest_run_is_the_main_line_and_the_rest_branch_off_it() {
+        let tkl = tseung_kwan_o();
+        assert_eq!(tkl.stations, codes(&["POA", "HAH", "TKO", "TIK", "YAT"]));
+        assert_eq!(tkl.branches, vec![codes(&["TKO", "LHP"])]);
+        assert_eq!(tkl.termini(), vec![&code("POA"), &code("YAT"), &code("LHP")]);
+    }
+
+    #[test]
+    fn trains_head_for_the_end_past_the_destination() {
+        let tkl = tseung_kwan_o();
+        assert_eq!(tkl.towards(&code("HAH"), &code("TIK")), Some(&code("YAT")));
+        assert_eq!(tkl.towards(&code("TIK"), &code("HAH")), Some(&code("POA")));
+        // onto the spur from either side of the junction, and back off it
+        assert_eq!(tkl.towards(&code("TIK"), &code("LHP")), Some(&code("LHP")));
+        assert_eq!(tkl.towards(&code("HAH"), &code("LHP")), Some(&code("LHP")));
+        assert_eq!(tkl.towards(&code("LHP"), &code("POA")), Some(&code("POA")));
+        assert_eq!(tkl.towards(&code("LHP"), &code("YAT")), Some(&code("YAT")));
+        assert_eq!(tkl.towards(&code("TIK"), &code("ADM")), None);
+    }
+
+    #[test]
+    fn csv_branches_start_at_their_junction() {
+        let graph = Graph::from_csv();
+        let tkl = &graph.lines[&code("TKL")];
+        assert_eq!(tkl.stations.first(), Some(&code("POA")));
+        assert_eq!(tkl.stations.last(), Some(&code("NOP")));
+        assert_eq!(tkl.branches, vec![codes(&["TKO", "LHP"])]);
+        assert_eq!(tkl.towards(&code("TIK"), &code("LHP")), Some(&code("LHP")));
+        assert_eq!(tkl.name, "Tseung Kwan O Line");
+    }
+
+    #[test]
+    fn loops_go_round_from_the_lowest_station() {
+        let circle = line("CIR", &[("AAA", "BBB"), ("BBB", "CCC"), ("CCC", "DDD"), ("DDD", "AAA")]);
+        assert_eq!(circle.stations, codes(&["AAA", "BBB", "CCC", "DDD"]));
+        assert!(circle.branches.is_empty());
+        assert_eq!(circle.towards(&code("BBB"), &code("DDD")), Some(&code("DDD")));
+        assert_eq!(circle.towards(&code("DDD"), &code("BBB")), Some(&code("AAA")));
+    }
+
+    #[test]
+    fn stretches_that_dont_join_are_taken_end_to_end() {
+        let split = line("SPL", &[("AAA", "BBB"), ("BBB", "CCC"), ("XXX", "YYY")]);
+        assert_eq!(split.stations, codes(&["AAA", "BBB", "CCC"]));
+        assert_eq!(split.branches, vec![codes(&["XXX", "YYY"])]);
+    }
+
+    #[test]
+    fn walking_is_not_a_line() {
+        assert!(!Graph::from_csv().lines.contains_key(&Code::walk()));
+
+        let connections = HashSet::from([
+            Connection(code("CEN"), code("HOK"), 8, Code::walk()),
+            Connection(code("CEN"), code("ADM"), 2, code("ISL")),
+        ]);
+        let lines = Line::from_connections(&connections);
+        assert_eq!(lines.keys().collect::<Vec<_>>(), vec![&code("ISL")]);
+    }
+
+    // local time in Hong Kong on Monday the 1st of January 2024, or the Saturday after
+    fn at(weekend: bool, hour: u32, minute: u32) -> DateTime<FixedOffset> {
+        let day = if weekend { 6 } else { 1 };
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 1, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn headways_change_at_the_rush_hour_and_late_night_boundaries() {
+        let headway = Headway {
+            peak: 2,
+            off_peak: 5,
+            late_night: 10,
+        };
+        let weekday = [
+            ((5, 59), 10),
+            ((6, 0), 5),
+            ((7, 29), 5),
+            ((7, 30), 2),
+            ((9, 29), 2),
+            ((9, 30), 5),
+            ((17, 30), 2),
+            ((19, 30), 5),
+            ((22, 59), 5),
+            ((23, 0), 10),
+        ];
+        for ((hour, minute), minutes) in weekday {
+            assert_eq!(headway.at(at(false, hour, minute)), minutes, "at {}:{:02}", hour, minute);
+        }
+        // no rush hours at the weekend, but the trains still stop for the night
+        assert_eq!(headway.at(at(true, 8, 0)), 5);
+        assert_eq!(headway.at(at(true, 18, 0)), 5);
+        assert_eq!(headway.at(at(true, 23, 30)), 10);
+        assert_eq!(headway.at(at(true, 5, 0)), 10);
+    }
+
+    #[test]
+    fn the_wait_is_half_the_headway_rounded_up() {
+        let headway = Headway {
+            peak: 2,
+            off_peak: 5,
+            late_night: 0,
+        };
+        assert_eq!(headway.wait(at(false, 8, 0)), 1);
+        assert_eq!(headway.wait(at(false, 12, 0)), 3);
+        assert_eq!(headway.wait(at(false, 0, 30)), 0);
+    }
+}
@@ -1,6 +1,10 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
-use axum::{extract::Query, response::IntoResponse, Json};
+use axum::{
+    extract::{ws::Message, Query},
+    response::IntoResponse,
+    Extension, Json,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tokio::sync::RwLock;
@@ -30,34 +34,127 @@ impl Location {
             timestamp: Some(chrono::Utc::now().timestamp()),
         }
     }
+
+    // Great circle distance to another location in metres, using the haversine formula.
+    pub fn distance(&self, other: &Location) -> f64 {
+        haversine(self.latitude, self.longitude, other.latitude, other.longitude)
+    }
+}
+
+const EARTH_RADIUS: f64 = 6_371_000.0; // metres
+
+pub fn haversine(lat1: f64, long1: f64, lat2: f64, long2: f64) -> f64 {
+    let dlat = (lat2 - lat1).to_radians();
+    let dlong = (long2 - long1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlong / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
 }
 
 pub async fn handle_location_op(json: &serde_json::Value, who: &str, game: &Arc<RwLock<Game>>) {
     let latitude = json.get("latitude").unwrap().as_f64().unwrap();
     let longitude = json.get("longitude").unwrap().as_f64().unwrap();
 
-    game.write()
-        .await
-        .get_mut_player(who)
-        .await
-        .unwrap()
-        .set_location(Location::new(latitude, longitude));
+    let mut game = game.write().await;
+    if game.state.read().await.is_paused() {
+        // positions are frozen while paused so nothing can be triggered
+        tracing::info!("Ignoring location from {} while paused", who);
+        return;
+    }
+
+    let location = Location::new(latitude, longitude);
+    let nearest = game
+        .graph
+        .nearest_station(&location, None)
+        .map(|(station, distance)| (station.clone(), distance));
+    let radius = game.settings.station_radius;
+
+    game.positions.insert(who.to_string(), latitude, longitude);
+    let player = game.get_mut_player(who).await.unwrap();
+    player.set_location(location);
+
+    // let the player know whenever they arrive at, leave or pass a different station
+    let Some((station, distance)) = nearest else {
+        return;
+    };
+    if !player.set_station(&station.code, distance <= radius) {
+        return;
+    }
+
+    let msg = json!({
+        "op": "station",
+        "code": station.code,
+        "name": station.name,
+        "distance": distance,
+        "at_station": distance <= radius,
+    });
+    if let Err(e) = player.send_msg(Message::Text(msg.to_string())).await {
+        tracing::error!("Failed to send the nearest station to {}: {}", who, e);
+    }
 }
 
+// How coordinates map onto the pixels of a network's map image, as a point known on both and the pixels per degree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Projection {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub x: i32,
+    pub y: i32,
+    pub x_per_degree: f64, // pixels per degree of longitude, negative if x grows westwards
+    pub y_per_degree: f64, // pixels per degree of latitude, negative as y grows southwards
+}
 
-// http post with query parameters lat and long return json x: i32, y: i32
-pub async fn convert(Query(location): Query<Location>) -> impl IntoResponse {
-    let (x, y) = converter(location.latitude, location.longitude);
-    Json(json!({ "x": x, "y": y }))
+// The MTR map, anchored on Lai Chi Kok
+impl Default for Projection {
+    fn default() -> Self {
+        Projection {
+            latitude: 22.3373,
+            longitude: 114.1482,
+            x: 1465,
+            y: 1359,
+            x_per_degree: -5767.02013,
+            y_per_degree: -6345.44055,
+        }
+    }
+}
+
+impl Projection {
+    pub fn project(&self, latitude: f64, longitude: f64) -> (i32, i32) {
+        let dy = (latitude - self.latitude) * self.y_per_degree;
+        let dx = (longitude - self.longitude) * self.x_per_degree;
+        (self.x + dx as i32, self.y + dy as i32)
+    }
+}
+
+// The area a network covers, any station outside it is almost certainly a typo in the data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bounds {
+    pub latitude: (f64, f64),  // south, north
+    pub longitude: (f64, f64), // west, east
 }
 
-pub fn converter(latitude: f64, longitude: f64) -> (i32, i32) {
-    // Lai Chi Kok lat: 22.3373 long: 114.1482 y: 1359 x: 1465
-    let dlat = 22.3373 - latitude;
-    let dlong = 114.1482 - longitude;
-    let dy = dlat * 6345.44055; // dy/dlat = -6345.44055
-    let dx = dlong * 5767.02013; // dx/dlong = 5767.020133
-    let y = 1359 + dy as i32;
-    let x = 1465 + dx as i32;
-    (x, y)
+// Rough box around Hong Kong
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds {
+            latitude: (22.15, 22.57),
+            longitude: (113.82, 114.45),
+        }
+    }
+}
+
+impl Bounds {
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        (self.latitude.0..=self.latitude.1).contains(&latitude)
+            && (self.longitude.0..=self.longitude.1).contains(&longitude)
+    }
+}
+
+// http get with query parameters latitude and longitude, returns json x: i32, y: i32 on the network's map
+pub async fn convert(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Query(location): Query<Location>,
+) -> impl IntoResponse {
+    let (x, y) = game.read().await.graph.projection.project(location.latitude, location.longitude);
+    Json(json!({ "x": x, "y": y }))
 }
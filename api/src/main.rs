@@ -1,28 +1,15 @@
-mod auth;
-mod db;
-mod game;
-mod location;
-mod logging;
-mod player;
-mod router;
-mod socket;
-mod teams;
-mod station;
-mod graph;
-mod states;
-mod state_machine;
-
 use std::sync::Arc;
 
-use axum::{extract::Extension, http::StatusCode, middleware, response::IntoResponse, Json};
-use serde_json::json;
-use state_machine::GameStateMachine;
-use states::{GameState, LobbyState};
+use api::{
+    game::Game,
+    router::router,
+    settings::Settings,
+    state_machine::GameStateMachine,
+    states::{GameState, LobbyState, State},
+};
+use axum::extract::Extension;
 use tokio::sync::RwLock;
 
-
-use crate::{game::Game, router::router, states::State};
-
 #[shuttle_runtime::main]
 pub async fn axum(
     #[shuttle_runtime::Secrets] secrets: shuttle_runtime::SecretStore,
@@ -30,25 +17,37 @@ pub async fn axum(
     // logging::init();
     let lobby_state = LobbyState::new();
     let state = Arc::new(RwLock::new(GameState::Lobby(lobby_state)));
+    let settings = Settings::from_secrets(&secrets);
+    let strict_network = settings.strict_network;
 
     let (game, rx) = Game::new(
         &secrets.get("EDGEDB_INSTANCE").unwrap(),
         &secrets.get("EDGEDB_SECRET_KEY").unwrap(),
         state.clone(),
+        settings,
     ).await;
 
+    // check the network data before anyone gets to play on it
+    let issues = game.read().await.graph.validate();
+    for issue in issues.iter() {
+        tracing::warn!("Network data: {}", issue);
+    }
+    if strict_network && !issues.is_empty() {
+        return Err(shuttle_runtime::CustomError::msg(format!(
+            "Refusing to start with {} problems in the network data",
+            issues.len()
+        ))
+        .into());
+    }
+
     {
         let mut s = state.write().await;
         if let GameState::Lobby(lobbystate) = &mut *s {
-            lobbystate.init(game.clone());
+            lobbystate.init(game.clone()).await;
         }
     }
 
-    let mut state_machine = GameStateMachine {
-        state,
-        rx,
-        game: game.clone(),
-    };
+    let mut state_machine = GameStateMachine::new(state, rx, game.clone()).await;
 
     tokio::spawn(async move {
         state_machine.run().await;
@@ -64,7 +63,3 @@ pub async fn axum(
 
     Ok(app.into())
 }
-
-async fn validate_token(Extension(username): Extension<String>) -> impl IntoResponse {
-    (StatusCode::OK, Json(json!({"username": username})))
-}
@@ -6,9 +6,9 @@ use axum::{
 use futures::{stream::SplitSink, SinkExt};
 use tokio::sync::RwLock;
 
-use crate::{location::Location, socket::Tx};
+use crate::{location::Location, socket::Tx, station::Code};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PlayerType {
     Hider,
     SecondarySeeker,
@@ -17,6 +17,12 @@ pub enum PlayerType {
     Spectator,
 }
 
+impl PlayerType {
+    pub fn is_seeker(&self) -> bool {
+        matches!(self, PlayerType::PrimarySeeker | PlayerType::SecondarySeeker)
+    }
+}
+
 #[derive(Debug)]
 pub struct Player {
     pub username: String, // kinda like backlink, guaranteed the same as the key for players
@@ -25,6 +31,7 @@ pub struct Player {
     pub ptype: PlayerType,
     pub stream: Option<Tx>,
     pub current_location: Option<Location>,
+    pub station: Option<(Code, bool)>, // nearest station, and whether they are within the station radius of it
     pub team: Option<String>,
 }
 
@@ -37,6 +44,7 @@ impl Player {
             connected: false,
             stream: None,
             current_location: None,
+            station: None,
             team: None,
         }
     }
@@ -62,6 +70,16 @@ impl Player {
         self.current_location = Some(location);
     }
 
+    // Remember the nearest station, true if it is a different one or they arrived at or left it.
+    pub fn set_station(&mut self, code: &Code, at_station: bool) -> bool {
+        let current = Some((code.clone(), at_station));
+        if self.station == current {
+            return false;
+        }
+        self.station = current;
+        true
+    }
+
     pub fn set_stream(&mut self, tx: SplitSink<WebSocket, Message>) {
         self.stream = Some(Arc::new(RwLock::new(tx)));
     }
@@ -79,3 +97,26 @@ impl Player {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn the_station_only_changes_on_a_new_station_or_arriving_or_leaving() {
+        let mut player = Player::new("alpha".to_string(), String::new());
+        let cen = Code::from_str("CEN").unwrap();
+        let adm = Code::from_str("ADM").unwrap();
+        assert!(player.set_station(&cen, false));
+        assert!(!player.set_station(&cen, false));
+        assert!(player.set_station(&cen, true));
+        assert!(!player.set_station(&cen, true));
+        assert!(player.set_station(&cen, false));
+        assert!(player.set_station(&adm, false));
+        assert!(!player.set_station(&adm, false));
+        assert!(player.set_station(&cen, false));
+        assert_eq!(player.station, Some((cen, false)));
+    }
+}
@@ -1,6 +1,7 @@
-use crate::*;
+use crate::{auth, disruption, export, fare, graph, line, location, scores, socket, state_machine, station, teams};
 use axum::{
-    routing::{get, patch, post},
+    middleware,
+    routing::{delete, get, patch, post},
     Router,
 };
 
@@ -13,7 +14,7 @@ pub fn router() -> Router {
         .route("/login", post(auth::login))
         .route(
             "/validate",
-            get(validate_token).layer(middleware::from_fn(auth::middleware)),
+            get(auth::validate_token).layer(middleware::from_fn(auth::middleware)),
         )
         .route(
             "/teams/:name",
@@ -34,10 +35,32 @@ pub fn router() -> Router {
             "/teams/:name",
             patch(teams::update_team_name).layer(middleware::from_fn(auth::middleware)),
         )
+        .route(
+            "/teams/:name/hider",
+            post(teams::set_hider).layer(middleware::from_fn(auth::middleware_admin)),
+        )
         .route("/teams", get(teams::getall))
         .route("/convert", get(location::convert))
         .route("/ws", get(socket::handler))
         .route("/stations", get(station::get))
+        .route("/stations.geojson", get(export::geojson))
+        .route("/lines", get(line::get))
+        .route("/route", get(graph::route))
+        .route("/fare", get(fare::get))
+        .route("/zone", get(graph::zone).layer(middleware::from_fn(auth::middleware)))
+        .route("/network/reload", post(graph::reload).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/network/closed", get(disruption::get))
+        .route("/network/closed", delete(disruption::clear).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/network/close", post(disruption::close).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/network/open", post(disruption::open).layer(middleware::from_fn(auth::middleware_admin)))
         .route("/state", get(state_machine::get))
+        .route("/scores", get(scores::get))
         .route("/start", post(state_machine::start).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/pause", post(state_machine::pause).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/resume", post(state_machine::resume).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/skip", post(state_machine::skip).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/restart", post(state_machine::restart).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/abort", post(state_machine::abort).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/reveal", post(state_machine::reveal).layer(middleware::from_fn(auth::middleware_admin)))
+        .route("/timer", post(state_machine::adjust_timer).layer(middleware::from_fn(auth::middleware_admin)))
 }
@@ -0,0 +1,134 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{response::IntoResponse, Extension, Json};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::{game::Game, station::Code};
+
+// The outcome of a single round, hiders score a point for every second they survive.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundResult {
+    pub round: usize,
+    pub hider: String,                  // the team that hid
+    pub survived: u64,                  // seconds into the seek phase before the last hider was caught
+    pub caught: bool,                   // whether every hider was found
+    pub caught_by: Option<String>,      // seeker that caught the last hider
+    pub capture_station: Option<Code>,  // station closest to the last capture
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Standing {
+    pub team: String,
+    pub score: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scoreboard {
+    pub rounds: Vec<RoundResult>,
+}
+
+impl Scoreboard {
+    pub fn record(&mut self, result: RoundResult) {
+        self.rounds.push(result);
+    }
+
+    pub fn last(&self) -> Option<&RoundResult> {
+        self.rounds.last()
+    }
+
+    // Throw away the result of a round that is being replayed.
+    pub fn discard(&mut self, round: usize) {
+        self.rounds.retain(|result| result.round != round);
+    }
+
+    pub fn clear(&mut self) {
+        self.rounds.clear();
+    }
+
+    // Cumulative scores for every team, highest first.
+    pub fn standings<'a>(&self, teams: impl Iterator<Item = &'a String>) -> Vec<Standing> {
+        let mut scores: HashMap<String, u64> = teams.map(|team| (team.clone(), 0)).collect();
+        for result in self.rounds.iter() {
+            *scores.entry(result.hider.clone()).or_insert(0) += result.survived;
+        }
+
+        let mut standings: Vec<Standing> = scores
+            .into_iter()
+            .map(|(team, score)| Standing { team, score })
+            .collect();
+        standings.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.team.cmp(&b.team)));
+        standings
+    }
+}
+
+pub async fn get(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    let game = game.read().await;
+    Json(json!({
+        "rounds": game.scores.rounds,
+        "standings": game.scores.standings(game.teams.keys()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(round: usize, hider: &str, survived: u64) -> RoundResult {
+        RoundResult {
+            round,
+            hider: hider.to_string(),
+            survived,
+            caught: true,
+            caught_by: None,
+            capture_station: None,
+        }
+    }
+
+    fn table(standings: Vec<Standing>) -> Vec<(String, u64)> {
+        standings.into_iter().map(|standing| (standing.team, standing.score)).collect()
+    }
+
+    #[test]
+    fn standings_add_up_the_seconds_survived_highest_first() {
+        let mut scores = Scoreboard::default();
+        scores.record(result(1, "alpha", 300));
+        scores.record(result(2, "bravo", 500));
+        scores.record(result(3, "alpha", 250));
+        let teams = ["alpha", "bravo", "charlie"].map(String::from);
+        assert_eq!(
+            table(scores.standings(teams.iter())),
+            vec![("alpha".to_string(), 550), ("bravo".to_string(), 500), ("charlie".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn ties_are_in_order_of_name() {
+        let mut scores = Scoreboard::default();
+        scores.record(result(1, "charlie", 120));
+        scores.record(result(2, "alpha", 120));
+        let teams = ["charlie", "alpha", "bravo"].map(String::from);
+        assert_eq!(
+            table(scores.standings(teams.iter())),
+            vec![("alpha".to_string(), 120), ("charlie".to_string(), 120), ("bravo".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn discarded_rounds_no_longer_count() {
+        let mut scores = Scoreboard::default();
+        scores.record(result(1, "alpha", 300));
+        scores.record(result(2, "bravo", 500));
+        scores.discard(2);
+        assert_eq!(scores.last().map(|result| result.round), Some(1));
+        let teams = ["alpha", "bravo"].map(String::from);
+        assert_eq!(
+            table(scores.standings(teams.iter())),
+            vec![("alpha".to_string(), 300), ("bravo".to_string(), 0)]
+        );
+
+        scores.clear();
+        assert!(scores.last().is_none());
+    }
+}
@@ -0,0 +1,82 @@
+use std::{str::FromStr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use shuttle_runtime::SecretStore;
+
+// Tunable game rules, all durations are in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    pub hide_duration: u64,      // how long the hiders get before the seekers are released
+    pub countdown_interval: u64, // how often a countdown tick is broadcasted
+    pub seek_duration: u64,      // how long the seekers have to catch the hiders
+    pub capture_radius: f64,     // metres between a seeker and a hider that counts as a capture
+    pub station_radius: f64,     // metres from a station that counts as being at it
+    pub walking: bool,           // whether journeys can walk between stations, both the walks in the data and generated ones
+    pub walk_distance: f64,      // furthest apart in metres two stations can be to walk between them
+    pub walking_speed: f64,      // metres per minute
+    pub intermission: u64,       // break after a round, and after the final standings
+    pub strict_network: bool,    // refuse to start if the network data has problems
+    pub network: Option<String>, // csv or ron file to load the network from instead of the built in one
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            hide_duration: 15 * 60,
+            countdown_interval: 10,
+            seek_duration: 60 * 60,
+            capture_radius: 20.0,
+            station_radius: 500.0,
+            walking: true,
+            walk_distance: 600.0,
+            walking_speed: 80.0,
+            intermission: 2 * 60,
+            strict_network: false,
+            network: None,
+        }
+    }
+}
+
+impl Settings {
+    // Read the settings from the secrets, falling back to the defaults for anything missing or invalid.
+    pub fn from_secrets(secrets: &SecretStore) -> Self {
+        let default = Settings::default();
+
+        Settings {
+            hide_duration: secret(secrets, "HIDE_DURATION", default.hide_duration),
+            countdown_interval: secret(secrets, "COUNTDOWN_INTERVAL", default.countdown_interval),
+            seek_duration: secret(secrets, "SEEK_DURATION", default.seek_duration),
+            capture_radius: secret(secrets, "CAPTURE_RADIUS", default.capture_radius),
+            station_radius: secret(secrets, "STATION_RADIUS", default.station_radius),
+            walking: secret(secrets, "WALKING", default.walking),
+            walk_distance: secret(secrets, "WALK_DISTANCE", default.walk_distance),
+            walking_speed: secret(secrets, "WALKING_SPEED", default.walking_speed),
+            intermission: secret(secrets, "INTERMISSION", default.intermission),
+            strict_network: secret(secrets, "STRICT_NETWORK", default.strict_network),
+            network: secrets.get("NETWORK_PATH"),
+        }
+    }
+
+    pub fn hide_duration(&self) -> Duration {
+        Duration::from_secs(self.hide_duration)
+    }
+
+    pub fn countdown_interval(&self) -> Duration {
+        Duration::from_secs(self.countdown_interval)
+    }
+
+    pub fn seek_duration(&self) -> Duration {
+        Duration::from_secs(self.seek_duration)
+    }
+
+    pub fn intermission(&self) -> Duration {
+        Duration::from_secs(self.intermission)
+    }
+}
+
+fn secret<T: FromStr>(secrets: &SecretStore, key: &str, fallback: T) -> T {
+    secrets
+        .get(key)
+        .and_then(|value| value.parse::<T>().ok())
+        .unwrap_or(fallback)
+}
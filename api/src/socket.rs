@@ -20,7 +20,9 @@ use tokio::sync::RwLock;
 use crate::{
     auth::{self, validate},
     game::Game,
+    graph,
     location::handle_location_op,
+    states::Snapshot,
 };
 
 pub type Tx = Arc<RwLock<SplitSink<WebSocket, Message>>>;
@@ -42,10 +44,11 @@ pub async fn handler(
 ) -> impl IntoResponse {
     let token = params.token;
 
-    return match validate(&token) {
+    match validate(&token) {
         Ok(token_data) => {
             let claims = token_data.claims;
             let username = claims.sub;
+            let admin = claims.admin;
             if !auth::authenticate(claims.exp, &username, &token, game.clone()).await {
                 return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()).into_response()
             } else if game.read().await.players[&username].connected {
@@ -59,14 +62,14 @@ pub async fn handler(
                 .unwrap()
                 .connected = true;
 
-            ws.on_upgrade(move |socket| websocket(socket, username, game))
+            ws.on_upgrade(move |socket| websocket(socket, username, admin, game))
               .into_response()
         }
         _ => (StatusCode::UNAUTHORIZED, "Invalid token".to_string()).into_response(),
-    };
+    }
 }
 
-pub async fn websocket(socket: WebSocket, who: String, game: Arc<RwLock<Game>>) {
+pub async fn websocket(socket: WebSocket, who: String, admin: bool, game: Arc<RwLock<Game>>) {
     let (mut tx, rx) = socket.split();
     if !ping(&mut tx, &who).await {
         return;
@@ -84,8 +87,19 @@ pub async fn websocket(socket: WebSocket, who: String, game: Arc<RwLock<Game>>)
         tracing::error!("Failed to connect to {}: {}", who, e);
     }
 
+    // catch the client up on the game in progress
+    {
+        let game = game.read().await;
+        // not a "state" op, the client would take it as the phase just starting
+        let mut snapshot = serde_json::to_value(Snapshot::new(&game).await).unwrap();
+        snapshot["op"] = json!("snapshot");
+        if let Err(e) = game.players[&who].send_msg(Message::Text(snapshot.to_string())).await {
+            tracing::error!("Failed to send the snapshot to {}: {}", who, e);
+        }
+    }
+
     tokio::spawn(async move {
-        handle_messages(rx, &who, game).await;
+        handle_messages(rx, &who, admin, game).await;
     });
 }
 
@@ -111,20 +125,20 @@ async fn assign_stream(game: &Arc<RwLock<Game>>, who: &str, tx: SplitSink<WebSoc
         .set_stream(tx);
 }
 
-async fn handle_messages(mut rx: SplitStream<WebSocket>, who: &str, game: Arc<RwLock<Game>>) {
+async fn handle_messages(mut rx: SplitStream<WebSocket>, who: &str, admin: bool, game: Arc<RwLock<Game>>) {
     let mut cnt = 0;
     while let Some(Ok(msg)) = rx.next().await {
         cnt += 1;
-        process_message(msg, who, &game).await;
+        process_message(msg, who, admin, &game).await;
     }
 
     tracing::info!("Connection with {} closed. {} messages", who, cnt);
     game.write().await.players.get_mut(who).unwrap().connected = false;
 }
 
-async fn process_message(msg: Message, who: &str, game: &Arc<RwLock<Game>>) {
+async fn process_message(msg: Message, who: &str, admin: bool, game: &Arc<RwLock<Game>>) {
     match msg {
-        Message::Text(text) => handle_json_message(text, who, game).await,
+        Message::Text(text) => handle_json_message(text, who, admin, game).await,
         Message::Binary(bin) => tracing::info!("Received binary message from {}: {:?}", who, bin),
         Message::Ping(ping) => tracing::info!("Received ping from {}: {:?}", who, ping),
         Message::Pong(pong) => tracing::info!("Received pong from {}: {:?}", who, pong),
@@ -132,7 +146,7 @@ async fn process_message(msg: Message, who: &str, game: &Arc<RwLock<Game>>) {
     }
 }
 
-async fn handle_json_message(text: String, who: &str, game: &Arc<RwLock<Game>>) {
+async fn handle_json_message(text: String, who: &str, admin: bool, game: &Arc<RwLock<Game>>) {
     tracing::info!("Received text message from {}: {}", who, text);
 
     let json = match serde_json::from_str::<serde_json::Value>(&text) {
@@ -149,6 +163,7 @@ async fn handle_json_message(text: String, who: &str, game: &Arc<RwLock<Game>>)
             "location" => handle_location_op(&json, who, game).await,
             "chat" => handle_chat_op(&json, who, game).await,
             "version" => handle_version_op(who, game).await,
+            "zone" => graph::handle_zone_op(&json, who, admin, game).await,
             _ => tracing::error!("Invalid operation from {}: {}", who, op),
         }
     }
@@ -0,0 +1,256 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::location::{haversine, Location};
+
+const METRES_PER_DEGREE: f64 = 111_320.0; // of latitude, and of longitude at the equator
+const CELL_SIZE: f64 = 0.005; // degrees, roughly 500m
+
+type Cell = (i64, i64);
+
+// Grid over coordinates so proximity checks only look at the few cells around a location,
+// instead of every station or player. Used for stations in the graph and for live player positions.
+#[derive(Debug, Clone)]
+pub struct Grid<K> {
+    cells: HashMap<Cell, Vec<K>>,
+    positions: HashMap<K, (f64, f64)>, // latitude and longitude of everything in the grid
+}
+
+impl<K> Default for Grid<K> {
+    fn default() -> Self {
+        Grid {
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+}
+
+fn cell(latitude: f64, longitude: f64) -> Cell {
+    ((latitude / CELL_SIZE).floor() as i64, (longitude / CELL_SIZE).floor() as i64)
+}
+
+impl<K: Clone + Eq + Hash> Grid<K> {
+    // Add something to the grid, or move it if it is already there.
+    pub fn insert(&mut self, key: K, latitude: f64, longitude: f64) {
+        if let Some(&(old_latitude, old_longitude)) = self.positions.get(&key) {
+            if cell(old_latitude, old_longitude) == cell(latitude, longitude) {
+                self.positions.insert(key, (latitude, longitude));
+                return;
+            }
+            self.remove(&key);
+        }
+        self.cells
+            .entry(cell(latitude, longitude))
+            .or_default()
+            .push(key.clone());
+        self.positions.insert(key, (latitude, longitude));
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        let Some((latitude, longitude)) = self.positions.remove(key) else {
+            return;
+        };
+        let cell = cell(latitude, longitude);
+        if let Some(keys) = self.cells.get_mut(&cell) {
+            keys.retain(|other| other != key);
+            if keys.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn distance(&self, key: &K, location: &Location) -> f64 {
+        let (latitude, longitude) = self.positions[key];
+        haversine(location.latitude, location.longitude, latitude, longitude)
+    }
+
+    // Everything in the cells exactly `rings` cells away from the centre, and how far each is in metres.
+    fn ring(&self, centre: Cell, rings: i64, location: &Location) -> Vec<(&K, f64)> {
+        let cells: Vec<Cell> = if rings == 0 {
+            vec![centre]
+        } else {
+            // only the edge of the square, the top and bottom rows then the sides between them
+            let (top, bottom) = (centre.0 - rings, centre.0 + rings);
+            let (left, right) = (centre.1 - rings, centre.1 + rings);
+            (left..=right)
+                .flat_map(|long| [(top, long), (bottom, long)])
+                .chain((top + 1..bottom).flat_map(|lat| [(lat, left), (lat, right)]))
+                .collect()
+        };
+        cells
+            .iter()
+            .filter_map(|cell| self.cells.get(cell))
+            .flatten()
+            .map(|key| (key, self.distance(key, location)))
+            .collect()
+    }
+
+    // Everything in the grid, closest first.
+    fn all(&self, location: &Location) -> Vec<(&K, f64)> {
+        let mut found: Vec<(&K, f64)> = self.positions.keys().map(|key| (key, self.distance(key, location))).collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        found
+    }
+
+    // How many rings of cells cover a distance in metres around a location.
+    fn rings(&self, location: &Location, metres: f64) -> i64 {
+        // longitude cells narrow away from the equator, so they set how many are needed
+        let cell_width = CELL_SIZE * METRES_PER_DEGREE * location.latitude.to_radians().cos().max(0.01);
+        (metres / cell_width).ceil().min(i64::MAX as f64) as i64
+    }
+
+    // Past this many rings looking at every cell costs more than looking at everything in the grid,
+    // which happens for locations far away from all of it, like a bad fix at 0, 0.
+    fn max_rings(&self) -> i64 {
+        (self.len() as f64).sqrt().ceil() as i64 + 1
+    }
+
+    // Everything within some metres of a location, closest first.
+    pub fn within(&self, location: &Location, metres: f64) -> Vec<(&K, f64)> {
+        let rings = self.rings(location, metres);
+        if rings > self.max_rings() {
+            let mut found = self.all(location);
+            found.retain(|(_, distance)| *distance <= metres);
+            return found;
+        }
+
+        let centre = cell(location.latitude, location.longitude);
+        let mut found: Vec<(&K, f64)> = (0..=rings)
+            .flat_map(|rings| self.ring(centre, rings, location))
+            .filter(|(_, distance)| *distance <= metres)
+            .collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        found
+    }
+
+    // The k closest things to a location, closest first.
+    pub fn nearest(&self, location: &Location, k: usize) -> Vec<(&K, f64)> {
+        let centre = cell(location.latitude, location.longitude);
+        let mut found: Vec<(&K, f64)> = vec![];
+        let mut rings = 0;
+        while found.len() < self.len().min(k) || self.could_be_closer(location, rings, &found, k) {
+            if rings > self.max_rings() {
+                found = self.all(location);
+                break;
+            }
+            found.extend(self.ring(centre, rings, location));
+            found.sort_by(|a, b| a.1.total_cmp(&b.1));
+            rings += 1;
+        }
+        found.truncate(k);
+        found
+    }
+
+    // Whether the next ring of cells could still hold something closer than the k closest so far.
+    fn could_be_closer(&self, location: &Location, rings: i64, found: &[(&K, f64)], k: usize) -> bool {
+        match found.get(k.saturating_sub(1)) {
+            Some((_, furthest)) if k > 0 => self.rings(location, *furthest) >= rings,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // stations roughly along Nathan Road, a few hundred metres apart
+    fn grid() -> Grid<&'static str> {
+        let mut grid = Grid::default();
+        grid.insert("TST", 22.2973, 114.1722);
+        grid.insert("JOR", 22.3049, 114.1717);
+        grid.insert("YMT", 22.3129, 114.1706);
+        grid.insert("MOK", 22.3193, 114.1694);
+        grid.insert("PRE", 22.3245, 114.1683);
+        grid
+    }
+
+    // every key in the grid by distance, worked out the slow way
+    fn brute_force<'a>(grid: &'a Grid<&'static str>, location: &Location) -> Vec<(&'a &'static str, f64)> {
+        let mut all: Vec<_> = grid
+            .positions
+            .iter()
+            .map(|(key, (latitude, longitude))| {
+                (key, haversine(location.latitude, location.longitude, *latitude, *longitude))
+            })
+            .collect();
+        all.sort_by(|a, b| a.1.total_cmp(&b.1));
+        all
+    }
+
+    fn keys(found: &[(&&'static str, f64)]) -> Vec<&'static str> {
+        found.iter().map(|(key, _)| **key).collect()
+    }
+
+    #[test]
+    fn empty_grid() {
+        let grid: Grid<&str> = Grid::default();
+        let location = Location::new(22.3, 114.17);
+        assert!(grid.is_empty());
+        assert!(grid.nearest(&location, 3).is_empty());
+        assert!(grid.within(&location, 10_000.0).is_empty());
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let grid = grid();
+        let location = Location::new(22.3180, 114.1700);
+        for k in 0..=grid.len() {
+            let expected = brute_force(&grid, &location);
+            assert_eq!(keys(&grid.nearest(&location, k)), keys(&expected[..k]));
+        }
+        assert_eq!(keys(&grid.nearest(&location, 1)), vec!["MOK"]);
+    }
+
+    #[test]
+    fn nearest_with_k_larger_than_the_grid() {
+        let grid = grid();
+        let location = Location::new(22.3000, 114.1720);
+        let found = grid.nearest(&location, 50);
+        assert_eq!(found.len(), grid.len());
+        assert_eq!(keys(&found), keys(&brute_force(&grid, &location)));
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        let grid = grid();
+        let location = Location::new(22.3100, 114.1710);
+        for metres in [0.0, 100.0, 500.0, 1_000.0, 2_000.0, 50_000.0] {
+            let expected: Vec<_> = brute_force(&grid, &location)
+                .into_iter()
+                .filter(|(_, distance)| *distance <= metres)
+                .collect();
+            assert_eq!(keys(&grid.within(&location, metres)), keys(&expected));
+        }
+    }
+
+    #[test]
+    fn far_away_query() {
+        let grid = grid();
+        let location = Location::new(0.0, 0.0); // a gps fix before it has locked on
+        assert_eq!(keys(&grid.nearest(&location, 1)), keys(&brute_force(&grid, &location)[..1]));
+        assert_eq!(grid.nearest(&location, 10).len(), grid.len());
+        assert!(grid.within(&location, 1_000.0).is_empty());
+        assert_eq!(grid.within(&location, 20_000_000.0).len(), grid.len());
+    }
+
+    #[test]
+    fn moving_and_removing() {
+        let mut grid = grid();
+        grid.insert("TST", 22.3240, 114.1680); // next to Prince Edward now
+        let location = Location::new(22.2973, 114.1722);
+        assert_eq!(keys(&grid.nearest(&location, 1)), vec!["JOR"]);
+
+        grid.remove(&"JOR");
+        assert_eq!(grid.len(), 4);
+        assert_eq!(keys(&grid.nearest(&location, 1)), vec!["YMT"]);
+        assert!(grid.within(&location, 500.0).is_empty());
+    }
+}
@@ -1,40 +1,118 @@
-use std::{sync::Arc, time::Duration};
+use std::{fmt, sync::Arc, time::Duration};
 
-use axum::{response::IntoResponse, Extension};
-use tokio::{sync::{mpsc, RwLock}, time::interval};
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{sync::{mpsc, RwLock}, time::{interval, MissedTickBehavior}};
 
-use crate::{game::Game, states::{State, GameState, HideState, LobbyState, RoundEndState, SeekState}};
+use crate::{game::Game, timer::Timer, states::{announce, sight_hiders, Sighting, State, GameState, Phase, Snapshot, GameOverState, HideState, LobbyState, RoundEndState, SeekState}};
 
+#[derive(Debug, PartialEq)]
 pub enum Event {
     Lobby,        // switch over to Lobby state,
     Seek,
     Hide,
     RoundEnd,        // switch over to RoundEnd state
+    GameOver,        // every team has hidden, show the final standings
+    Pause,           // freeze the timer of the current state
+    Resume,
+    Skip,            // run out the timer of the current state
+    Restart,         // replay the current round with the same hiders
+    AdjustTimer(i64), // add or remove seconds from the current timer
 }
 
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Lobby => "Lobby",
+            Event::Seek => "Seek",
+            Event::Hide => "Hide",
+            Event::RoundEnd => "RoundEnd",
+            Event::GameOver => "GameOver",
+            Event::Pause => "Pause",
+            Event::Resume => "Resume",
+            Event::Skip => "Skip",
+            Event::Restart => "Restart",
+            Event::AdjustTimer(_) => "AdjustTimer",
+        }
+    }
+}
+
+// An event that is not allowed in the current phase.
+#[derive(Debug, PartialEq)]
+pub struct InvalidTransition {
+    pub from: Phase,
+    pub event: &'static str,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cannot {} during {}", self.event, self.from)
+    }
+}
+
+// The transition table, which phase an event leads to from each phase.
+// Pausing and adjusting the timer keep the phase, and need a timer to act on.
+pub fn transition(from: Phase, event: &Event) -> Result<Phase, InvalidTransition> {
+    use Phase::*;
+    match (from, event) {
+        (Lobby, Event::Hide) => Ok(Hide),
+        (Hide, Event::Seek) => Ok(Seek),
+        (Seek, Event::RoundEnd) => Ok(RoundEnd),
+        (RoundEnd, Event::Hide) => Ok(Hide),
+        (RoundEnd, Event::GameOver) => Ok(GameOver),
+        (Hide | Seek | RoundEnd | GameOver, Event::Lobby) => Ok(Lobby),
+        (Hide | Seek | RoundEnd, Event::Restart) => Ok(Hide),
+        (
+            Hide | Seek | RoundEnd | GameOver,
+            Event::Pause | Event::Resume | Event::Skip | Event::AdjustTimer(_),
+        ) => Ok(from),
+        _ => Err(InvalidTransition {
+            from,
+            event: event.name(),
+        }),
+    }
+}
+
+const TICK_RATE: Duration = Duration::from_millis(500); // how often the current state is updated
+
+// Owns the live state and drives it, everyone else only ever sees a published copy of it.
 pub struct GameStateMachine {
-    pub state: Arc<RwLock<GameState>>, // current state
+    current: GameState, // live state, updated in place every tick
+    pub state: Arc<RwLock<GameState>>, // snapshot of the current state shared with the rest of the server
     pub rx: mpsc::Receiver<Event>, // event channel, recieve events
     pub game: Arc<RwLock<Game>>,
 }
 
 impl GameStateMachine {
-    pub async fn update_state(&self, new_state: GameState) {
+    pub async fn new(state: Arc<RwLock<GameState>>, rx: mpsc::Receiver<Event>, game: Arc<RwLock<Game>>) -> Self {
+        let current = state.read().await.clone();
+        GameStateMachine {
+            current,
+            state,
+            rx,
+            game,
+        }
+    }
+
+    // Copy the live state out so that handlers can read it.
+    async fn publish(&self) {
         let mut state = self.state.write().await;
-        *state = new_state;
+        *state = self.current.clone();
     }
 
-    pub async fn get_state(&self) -> GameState {
-        self.state.read().await.clone()
+    async fn enter(&mut self, new_state: GameState) {
+        self.current = new_state;
+        self.publish().await;
     }
 
     pub async fn run(&mut self) {
-        let mut interval = interval(Duration::from_millis(500));
+        let mut interval = interval(TICK_RATE);
+        // a slow tick shouldn't cause a burst of catch up ticks afterwards
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
         loop {
-            interval.tick().await;
             tokio::select! {
                 Some(event) = self.rx.recv() => {
-                    println!("got a event");
                     self.handle_event(event).await;
                 }
                 _ = interval.tick() => {
@@ -46,52 +124,325 @@ impl GameStateMachine {
 
     // init deinit code
     async fn handle_event(&mut self, event: Event) {
+        if let Err(e) = transition(self.current.phase(), &event) {
+            tracing::error!("Ignoring event. {}", e);
+            return;
+        }
+
         match event {
             Event::Lobby => {
                 let mut lobby_state = LobbyState::new();
-                lobby_state.init(self.game.clone());
-                self.update_state(GameState::Lobby(lobby_state)).await;
+                lobby_state.init(self.game.clone()).await;
+                self.enter(GameState::Lobby(lobby_state)).await;
             }
             Event::Hide => {
                 let mut hide_state = HideState::new();
-                hide_state.init(self.game.clone());
-                self.update_state(GameState::Hide(hide_state)).await;
+                hide_state.init(self.game.clone()).await;
+                self.enter(GameState::Hide(hide_state)).await;
             }
             Event::Seek => {
                 let mut seek_state = SeekState::new();
-                seek_state.init(self.game.clone());
-                self.update_state(GameState::Seek(seek_state)).await;
+                seek_state.init(self.game.clone()).await;
+                self.enter(GameState::Seek(seek_state)).await;
             }
             Event::RoundEnd => {
                 let mut round_end_state = RoundEndState::new();
-                round_end_state.init(self.game.clone());
-                self.update_state(GameState::RoundEnd(round_end_state)).await;
+                round_end_state.init(self.game.clone()).await;
+                self.enter(GameState::RoundEnd(round_end_state)).await;
+            }
+            Event::GameOver => {
+                let mut game_over_state = GameOverState::new();
+                game_over_state.init(self.game.clone()).await;
+                self.enter(GameState::GameOver(game_over_state)).await;
+            }
+            Event::Restart => {
+                self.game.write().await.restart_round();
+                let mut hide_state = HideState::new();
+                hide_state.init(self.game.clone()).await;
+                self.enter(GameState::Hide(hide_state)).await;
             }
+            Event::Pause => self.set_paused(true).await,
+            Event::Resume => self.set_paused(false).await,
+            Event::Skip => {
+                // nothing happens while paused, so carry on first and let everyone know
+                self.set_paused(false).await;
+                self.adjust_timer(|timer| timer.finish()).await
+            }
+            Event::AdjustTimer(seconds) => self.adjust_timer(|timer| timer.adjust(seconds)).await,
+        }
+    }
+
+    // Change the timer of the current state, the state itself notices when it runs out.
+    async fn adjust_timer(&mut self, change: impl FnOnce(&mut Timer)) {
+        let name = self.current.phase().to_string();
+        let Some(timer) = self.current.timer_mut() else {
+            tracing::error!("There is no timer in the lobby");
+            return;
+        };
+        change(timer);
+        let remaining = timer.remaining();
+        self.publish().await;
+
+        announce(&self.game, json!({
+            "op": "countdown",
+            "state": name,
+            "remaining": remaining.as_secs(),
+        }))
+        .await;
+    }
+
+    async fn set_paused(&mut self, paused: bool) {
+        let Some(timer) = self.current.timer_mut() else {
+            tracing::error!("Nothing to pause or resume in the lobby");
+            return;
+        };
+        if timer.is_paused() == paused {
+            return;
+        }
+        if paused {
+            timer.pause();
+        } else {
+            timer.resume();
         }
+        let remaining = timer.remaining();
+        self.publish().await;
+
+        tracing::info!("Game {}", if paused { "paused" } else { "resumed" });
+        announce(&self.game, json!({
+            "op": if paused { "paused" } else { "resumed" },
+            "remaining": remaining.as_secs(),
+        }))
+        .await;
     }
 
     // loop code
     async fn process_state(&mut self) {
-        match self.get_state().await {
-            GameState::Lobby(mut state) => state.update(self.game.clone()).await,
-            GameState::Hide(mut state) => state.update(self.game.clone()).await,
-            GameState::Seek(mut state) => state.update(self.game.clone()).await,
-            GameState::RoundEnd(mut state) => state.update(self.game.clone()).await,
+        if self.current.is_paused() {
+            return;
+        }
+        match &mut self.current {
+            GameState::Lobby(state) => state.update(self.game.clone()).await,
+            GameState::Hide(state) => state.update(self.game.clone()).await,
+            GameState::Seek(state) => state.update(self.game.clone()).await,
+            GameState::RoundEnd(state) => state.update(self.game.clone()).await,
+            GameState::GameOver(state) => state.update(self.game.clone()).await,
         }
+        self.publish().await;
     }
-
-    // TODO! implement pausing and resuming?
 }
 
 pub async fn get(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
     // router function to get the current gamestate
-    game.read().await.state.read().await.clone()
+    Json(Snapshot::new(&*game.read().await).await)
 }
 
 pub async fn start(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
-    // switch from lobby to hide
-    if let Err(e) = game.read().await.tx.send(Event::Hide).await {
-        return "error"
+    // switch from lobby to hide, starting a new session
+    {
+        let mut game = game.write().await;
+        if let Err(e) = check(&game, &Event::Hide).await {
+            return e;
+        }
+        // one team to hide and at least one to seek
+        if game.teams.len() < 2 {
+            return (StatusCode::CONFLICT, Json(json!({"error": "At least two teams are needed to start a game"})));
+        }
+        game.new_session();
+    }
+    send(&game, Event::Hide, "Game started").await
+}
+
+// admin only, freeze the timers of the current phase
+pub async fn pause(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    if game.read().await.state.read().await.is_paused() {
+        return (StatusCode::CONFLICT, Json(json!({"error": "The game is already paused"})));
+    }
+    send(&game, Event::Pause, "Game paused").await
+}
+
+// admin only, carry on with exactly the time that was left
+pub async fn resume(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    if !game.read().await.state.read().await.is_paused() {
+        return (StatusCode::CONFLICT, Json(json!({"error": "The game is not paused"})));
+    }
+    send(&game, Event::Resume, "Game resumed").await
+}
+
+// admin only, show the seekers which stations the hiders are at, their zone grows from there
+pub async fn reveal(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    let mut game = game.write().await;
+    let elapsed = {
+        let state = game.state.read().await;
+        if state.phase() != Phase::Seek {
+            return (StatusCode::CONFLICT, Json(json!({"error": "The hiders can only be revealed while seeking"})));
+        }
+        state.timer().map(|timer| timer.elapsed()).unwrap_or_default()
+    };
+
+    let stations = sight_hiders(&game);
+    if stations.is_empty() {
+        return (StatusCode::CONFLICT, Json(json!({"error": "No hider has sent a location yet"})));
+    }
+    tracing::info!("Revealed the hiders at {:?}", stations);
+    game.sighting = Some(Sighting {
+        stations: stations.clone(),
+        elapsed,
+    });
+    (StatusCode::OK, Json(json!({"message": "Hiders revealed", "stations": stations})))
+}
+
+#[derive(Deserialize)]
+pub struct TimerChange {
+    seconds: i64, // positive to extend, negative to shorten
+}
+
+// admin only, move on to the next phase right away
+pub async fn skip(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    send(&game, Event::Skip, "Skipped to the next phase").await
+}
+
+// admin only, play the current round again from the start of the hide phase
+pub async fn restart(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    send(&game, Event::Restart, "Round restarted").await
+}
+
+// admin only, stop the game and go back to the lobby
+pub async fn abort(Extension(game): Extension<Arc<RwLock<Game>>>) -> impl IntoResponse {
+    send(&game, Event::Lobby, "Game aborted").await
+}
+
+// admin only, extend or shorten the running timer
+pub async fn adjust_timer(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Json(change): Json<TimerChange>,
+) -> impl IntoResponse {
+    // no phase is longer than a whole round, so neither is any sensible change
+    let limit = {
+        let settings = &game.read().await.settings;
+        settings.hide_duration.saturating_add(settings.seek_duration)
+    };
+    if change.seconds.unsigned_abs() > limit {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("The timer can be changed by at most {} seconds", limit)})),
+        );
+    }
+    send(&game, Event::AdjustTimer(change.seconds), "Timer adjusted").await
+}
+
+type Reply = (StatusCode, Json<serde_json::Value>);
+
+// Make sure the event is allowed in the current state before handing it to the state machine.
+async fn check(game: &Game, event: &Event) -> Result<Phase, Reply> {
+    let phase = game.state.read().await.phase();
+    transition(phase, event).map_err(|e| {
+        tracing::error!("Rejected request. {}", e);
+        (StatusCode::CONFLICT, Json(json!({"error": e.to_string()})))
+    })
+}
+
+async fn send(game: &Arc<RwLock<Game>>, event: Event, message: &str) -> Reply {
+    let tx = {
+        let game = game.read().await;
+        if let Err(e) = check(&game, &event).await {
+            return e;
+        }
+        game.tx.clone()
+    };
+
+    // don't hold on to the game while waiting, the state machine may need it to make room
+    if let Err(e) = tx.send(event).await {
+        tracing::error!("Failed to send event to the state machine. {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "Failed to reach the state machine"})),
+        );
+    }
+    (StatusCode::OK, Json(json!({"message": message})))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHASES: [Phase; 5] = [
+        Phase::Lobby,
+        Phase::Hide,
+        Phase::Seek,
+        Phase::RoundEnd,
+        Phase::GameOver,
+    ];
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::Lobby,
+            Event::Seek,
+            Event::Hide,
+            Event::RoundEnd,
+            Event::GameOver,
+            Event::Pause,
+            Event::Resume,
+            Event::Skip,
+            Event::Restart,
+            Event::AdjustTimer(30),
+        ]
+    }
+
+    // every legal (from, event, to), anything not listed must be rejected
+    fn legal() -> Vec<(Phase, Event, Phase)> {
+        use Phase::*;
+        let mut legal = vec![
+            (Lobby, Event::Hide, Hide),
+            (Hide, Event::Seek, Seek),
+            (Seek, Event::RoundEnd, RoundEnd),
+            (RoundEnd, Event::Hide, Hide),
+            (RoundEnd, Event::GameOver, GameOver),
+            (GameOver, Event::Lobby, Lobby),
+            (Hide, Event::Lobby, Lobby),
+            (Seek, Event::Lobby, Lobby),
+            (RoundEnd, Event::Lobby, Lobby),
+            (Hide, Event::Restart, Hide),
+            (Seek, Event::Restart, Hide),
+            (RoundEnd, Event::Restart, Hide),
+        ];
+        for phase in [Hide, Seek, RoundEnd, GameOver] {
+            legal.push((phase, Event::Pause, phase));
+            legal.push((phase, Event::Resume, phase));
+            legal.push((phase, Event::Skip, phase));
+            legal.push((phase, Event::AdjustTimer(30), phase));
+        }
+        legal
+    }
+
+    #[test]
+    fn legal_transitions() {
+        for (from, event, to) in legal() {
+            assert_eq!(transition(from, &event), Ok(to), "{:?} during {:?}", event, from);
+        }
+    }
+
+    #[test]
+    fn illegal_transitions() {
+        let legal = legal();
+        for from in PHASES {
+            for event in events() {
+                if legal.iter().any(|(f, e, _)| *f == from && e == &event) {
+                    continue;
+                }
+                assert_eq!(
+                    transition(from, &event),
+                    Err(InvalidTransition { from, event: event.name() }),
+                    "{:?} during {:?}",
+                    event,
+                    from
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn start_is_rejected_mid_round() {
+        let error = transition(Phase::Seek, &Event::Hide).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot Hide during Seek");
     }
-    "success"
 }
@@ -1,11 +1,27 @@
 use axum::extract::ws::Message;
 use serde_json::json;
-use std::sync::Arc;
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::RwLock;
 
-use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 
-use crate::game::Game;
+use crate::{
+    disruption,
+    game::Game,
+    graph::Zone,
+    location::Location,
+    player::PlayerType,
+    scores::{RoundResult, Standing},
+    state_machine::Event,
+    station::Code,
+    teams::TeamType,
+    timer::Timer,
+};
 
 #[derive(Clone)]
 pub enum GameState {
@@ -13,17 +29,108 @@ pub enum GameState {
     Hide(HideState),
     Seek(SeekState),
     RoundEnd(RoundEndState),
+    GameOver(GameOverState),
 }
 
-impl IntoResponse for GameState {
-    fn into_response(self) -> Response {
+// Everything a client needs to pick the game back up, e.g. after reconnecting mid round.
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub state: Phase,
+    pub round: usize,
+    pub started_at: Option<DateTime<Utc>>, // start of the current phase
+    pub ends_at: Option<DateTime<Utc>>,    // expected end of the current phase
+    pub remaining: Option<u64>,            // seconds left in the current phase
+    pub paused: bool,
+    pub hider: Option<String>,
+    pub seekers: Vec<String>,
+    pub scores: Vec<Standing>,
+}
+
+impl Snapshot {
+    pub async fn new(game: &Game) -> Self {
+        let state = game.state.read().await;
+        let timer = state.timer();
+
+        let mut seekers: Vec<String> = game
+            .teams
+            .values()
+            .filter(|team| matches!(team.ttype, TeamType::Seeker))
+            .map(|team| team.name.clone())
+            .collect();
+        seekers.sort();
+
+        Snapshot {
+            state: state.phase(),
+            round: game.round,
+            started_at: timer.map(|timer| timer.started_at()),
+            ends_at: timer.and_then(|timer| timer.ends_at()),
+            remaining: timer.map(|timer| timer.remaining().as_secs()),
+            paused: state.is_paused(),
+            hider: game.hider_team().map(|team| team.name.clone()),
+            seekers,
+            scores: game.scores.standings(game.teams.keys()),
+        }
+    }
+}
+
+// The kind of state the game is in, without any of its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Phase {
+    Lobby,
+    Hide,
+    Seek,
+    RoundEnd,
+    GameOver,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::Lobby => "Lobby",
+            Phase::Hide => "Hide",
+            Phase::Seek => "Seek",
+            Phase::RoundEnd => "RoundEnd",
+            Phase::GameOver => "GameOver",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl GameState {
+    pub fn phase(&self) -> Phase {
+        match self {
+            GameState::Lobby(_) => Phase::Lobby,
+            GameState::Hide(_) => Phase::Hide,
+            GameState::Seek(_) => Phase::Seek,
+            GameState::RoundEnd(_) => Phase::RoundEnd,
+            GameState::GameOver(_) => Phase::GameOver,
+        }
+    }
+
+    // The countdown of the current phase, the lobby has none.
+    pub fn timer(&self) -> Option<&Timer> {
+        match self {
+            GameState::Lobby(_) => None,
+            GameState::Hide(state) => Some(&state.timer),
+            GameState::Seek(state) => Some(&state.timer),
+            GameState::RoundEnd(state) => Some(&state.timer),
+            GameState::GameOver(state) => Some(&state.timer),
+        }
+    }
+
+    pub fn timer_mut(&mut self) -> Option<&mut Timer> {
         match self {
-            GameState::Lobby(_) => "Lobby".into_response(),
-            GameState::Hide(_) => "Hide".into_response(),
-            GameState::Seek(_) => "Seek".into_response(),
-            GameState::RoundEnd(_) => "RoundEnd".into_response(),
+            GameState::Lobby(_) => None,
+            GameState::Hide(state) => Some(&mut state.timer),
+            GameState::Seek(state) => Some(&mut state.timer),
+            GameState::RoundEnd(state) => Some(&mut state.timer),
+            GameState::GameOver(state) => Some(&mut state.timer),
         }
     }
+
+    pub fn is_paused(&self) -> bool {
+        self.timer().is_some_and(|timer| timer.is_paused())
+    }
 }
 
 #[async_trait::async_trait]
@@ -33,30 +140,30 @@ pub trait State {
     fn new() -> Self;
 }
 
+// Send a json message to every connected player.
+pub async fn announce(game: &Arc<RwLock<Game>>, msg: serde_json::Value) {
+    let msg = serde_json::to_string(&msg).unwrap();
+    if let Err(e) = game.read().await.broadcast(Message::Text(msg)).await {
+        tracing::error!("Failed to update the state. {}", e);
+    }
+}
+
 #[derive(Clone)]
 pub struct LobbyState;
 #[async_trait::async_trait]
 impl State for LobbyState {
     async fn init(&mut self, game: Arc<RwLock<Game>>) {
-        let state = serde_json::to_string(&json!({
+        announce(&game, json!({
             "op": "state",
             "state": "Lobby",
         }))
-        .unwrap();
-
-        if let Err(e) = game
-            .write()
-            .await
-            .broadcast(Message::Text(state))
-            .await {
-            tracing::error!("Failed to update the state. {}", e);
-        }
-        println!("Init Lobby state");
-        // Initialize the hider seeker lists
+        .await;
+        tracing::info!("Init Lobby state");
+        game.write().await.reset_roles();
+        disruption::reopen(&game).await; // closures only last for the game they were made in
     }
 
-    async fn update(&mut self, game: Arc<RwLock<Game>>) {
-        println!("Lobby state");
+    async fn update(&mut self, _game: Arc<RwLock<Game>>) {
         // account for new teams?
     }
 
@@ -66,101 +173,594 @@ impl State for LobbyState {
 }
 
 #[derive(Clone)]
-pub struct HideState;
+pub struct HideState {
+    pub timer: Timer,
+    last_countdown: Instant, // when the last countdown tick was broadcasted
+    done: bool,              // the event ending the phase has been sent
+}
+
+impl HideState {
+    pub fn remaining(&self) -> Duration {
+        self.timer.remaining()
+    }
+}
+
 #[async_trait::async_trait]
 impl State for HideState {
     async fn init(&mut self, game: Arc<RwLock<Game>>) {
-        let state = serde_json::to_string(&json!({
+        let duration = game.read().await.settings.hide_duration();
+        self.timer = Timer::new(duration);
+        self.last_countdown = Instant::now();
+        tracing::info!("Init Hide state");
+
+        let assignment = {
+            let mut game = game.write().await;
+            game.round += 1;
+            match game.pick_hiders() {
+                Some(hider) => {
+                    game.assign_roles(&hider);
+                    let mut seekers: Vec<String> = game
+                        .teams
+                        .keys()
+                        .filter(|name| **name != hider)
+                        .cloned()
+                        .collect();
+                    seekers.sort();
+                    Some((hider, seekers))
+                }
+                None => {
+                    game.round -= 1; // nothing was played
+                    None
+                }
+            }
+        };
+
+        // a round needs someone to hide, without one there is nothing to play so go back to the lobby
+        let Some((hider, seekers)) = assignment else {
+            tracing::error!("No team left to hide, going back to the lobby");
+            self.done = true;
+            if let Err(e) = game.read().await.tx.try_send(Event::Lobby) {
+                tracing::error!("Failed to return to the lobby. {}", e);
+            }
+            return;
+        };
+
+        announce(&game, json!({
             "op": "state",
             "state": "Hide",
+            "duration": duration.as_secs(),
         }))
-        .unwrap();
-
-        if let Err(e) = game
-            .write()
-            .await
-            .broadcast(Message::Text(state))
-            .await {
-            tracing::error!("Failed to update the state. {}", e);
-        }
-        println!("Init hide state");
+        .await;
 
-        // pick hiders
-        // Request for hiders base
-        // START TIMER TO CHANGE
+        tracing::info!("{} are hiding this round", hider);
+        let round = game.read().await.round;
+        announce(&game, json!({
+            "op": "hiders",
+            "round": round,
+            "hider": hider,
+            "seekers": seekers,
+        }))
+        .await;
     }
 
     async fn update(&mut self, game: Arc<RwLock<Game>>) {
-        println!("Hide state");
-        // CHECK TIMER FOR CHANGEOVER
+        if self.done {
+            return;
+        }
+
+        if self.timer.is_finished() {
+            // hiding time is over, release the seekers
+            if let Err(e) = game.read().await.tx.try_send(Event::Seek) {
+                tracing::error!("Failed to end the hide phase. {}", e);
+                return;
+            }
+            self.done = true;
+            return;
+        }
+
+        let interval = game.read().await.settings.countdown_interval();
+        if self.last_countdown.elapsed() >= interval {
+            self.last_countdown = Instant::now();
+            announce(&game, json!({
+                "op": "countdown",
+                "state": "Hide",
+                "remaining": self.remaining().as_secs(),
+            }))
+            .await;
+        }
     }
 
     fn new() -> Self {
-        HideState
+        HideState {
+            timer: Timer::new(Duration::ZERO),
+            last_countdown: Instant::now(),
+            done: false,
+        }
     }
 }
 
+// A hider that was found during the seek phase.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub hider: String,
+    pub seeker: String,
+    pub location: Location,
+    pub station: Option<Code>, // closest station to where it happened
+    pub elapsed: Duration,     // time into the seek phase
+}
+
+// Where the hiders were last revealed to the seekers.
+#[derive(Debug, Clone)]
+pub struct Sighting {
+    pub stations: Vec<Code>, // nearest station to each hider at the time
+    pub elapsed: Duration,   // time into the seek phase
+}
+
+// The stations the hiders are nearest to right now, for revealing them.
+pub fn sight_hiders(game: &Game) -> Vec<Code> {
+    let mut stations: Vec<Code> = game
+        .players
+        .values()
+        .filter(|player| matches!(player.ptype, PlayerType::Hider))
+        .filter_map(|hider| hider.current_location.as_ref())
+        .filter_map(|location| game.graph.nearest_station(location, None))
+        .map(|(station, _)| station.code.clone())
+        .collect();
+    stations.sort();
+    stations.dedup();
+    stations
+}
+
 #[derive(Clone)]
-pub struct SeekState;
+pub struct SeekState {
+    pub timer: Timer,
+    pub captures: Vec<Capture>,
+    zone_sent: Option<(Duration, u64)>, // sighting and minute after it of the last zone sent to the seekers
+    last_countdown: Instant,
+    done: bool, // the RoundEnd event has been sent
+}
+
+impl SeekState {
+    pub fn remaining(&self) -> Duration {
+        self.timer.remaining()
+    }
+
+    pub fn is_captured(&self, hider: &str) -> bool {
+        self.captures.iter().any(|capture| capture.hider == hider)
+    }
+
+    // Score the round for the hiding team.
+    async fn record_result(&self, game: &Arc<RwLock<Game>>, caught: bool) {
+        let mut game = game.write().await;
+        let Some(hider) = game.hider_team().map(|team| team.name.clone()) else {
+            tracing::error!("No hider team to score this round");
+            return;
+        };
+
+        let last = self.captures.last().filter(|_| caught);
+        let result = RoundResult {
+            round: game.round,
+            hider,
+            survived: match last {
+                Some(capture) => capture.elapsed.as_secs(),
+                None => self.timer.elapsed().as_secs(),
+            },
+            caught,
+            caught_by: last.map(|capture| capture.seeker.clone()),
+            capture_station: last.and_then(|capture| capture.station.clone()),
+        };
+        game.scores.record(result);
+    }
+
+    // Tell the seekers where the hiders could have got to since they were last revealed, once a minute.
+    // Nothing is sent until they have been revealed.
+    async fn send_zone(&mut self, game: &Arc<RwLock<Game>>) {
+        let game = game.read().await;
+        let Some(zone) = self.next_zone(&game, self.timer.elapsed()) else {
+            return;
+        };
+
+        let mut msg = json!(zone);
+        msg["op"] = json!("zone");
+        let msg = Message::Text(msg.to_string());
+        for seeker in game.players.values().filter(|player| player.ptype.is_seeker() && player.connected) {
+            if let Err(e) = seeker.send_msg(msg.clone()).await {
+                tracing::error!("Failed to send the zone to {}: {}", seeker.username, e);
+            }
+        }
+    }
+
+    // The zone at some time into the seek phase, if it has grown by a minute or the hiders were revealed again
+    // since the last one.
+    fn next_zone(&mut self, game: &Game, elapsed: Duration) -> Option<Zone> {
+        let sighting = game.sighting.as_ref()?;
+        let minute = elapsed.saturating_sub(sighting.elapsed).as_secs() / 60;
+        if self.zone_sent == Some((sighting.elapsed, minute)) {
+            return None;
+        }
+        self.zone_sent = Some((sighting.elapsed, minute));
+        Some(game.graph.zone(&sighting.stations, minute as usize))
+    }
+
+    // Pair up every free hider with a seeker standing close enough to them.
+    fn find_captures(&self, game: &Game) -> Vec<Capture> {
+        let radius = game.settings.capture_radius;
+
+        game.players
+            .values()
+            .filter(|player| matches!(player.ptype, PlayerType::Hider) && !self.is_captured(&player.username))
+            .filter_map(|hider| {
+                let location = hider.current_location.as_ref()?;
+                // closest seeker first
+                let seeker = game
+                    .positions
+                    .within(location, radius)
+                    .into_iter()
+                    .filter_map(|(username, _)| game.players.get(username))
+                    .find(|player| player.ptype.is_seeker())?;
+                Some(Capture {
+                    hider: hider.username.clone(),
+                    seeker: seeker.username.clone(),
+                    location: location.clone(),
+                    station: game.graph.nearest_station(location, None).map(|(station, _)| station.code.clone()),
+                    elapsed: self.timer.elapsed(),
+                })
+            })
+            .collect()
+    }
+}
+
 #[async_trait::async_trait]
 impl State for SeekState {
     async fn init(&mut self, game: Arc<RwLock<Game>>) {
-        let state = serde_json::to_string(&json!({
+        let duration = game.read().await.settings.seek_duration();
+        self.timer = Timer::new(duration);
+        self.last_countdown = Instant::now();
+        game.write().await.sighting = None; // the hiders stay hidden until they are revealed
+        self.zone_sent = None;
+
+        announce(&game, json!({
             "op": "state",
             "state": "Seek",
+            "duration": duration.as_secs(),
         }))
-        .unwrap();
+        .await;
+        tracing::info!("Init Seek state");
+    }
 
-        if let Err(e) = game
-            .write()
-            .await
-            .broadcast(Message::Text(state))
-            .await {
-            tracing::error!("Failed to update the state. {}", e);
+    async fn update(&mut self, game: Arc<RwLock<Game>>) {
+        if self.done {
+            return;
         }
-        println!("Init Seek state");
 
-        // START TIMER
-    }
+        let (captures, hiders) = {
+            let game = game.read().await;
+            let hiders = game
+                .players
+                .values()
+                .filter(|player| matches!(player.ptype, PlayerType::Hider))
+                .count();
+            (self.find_captures(&game), hiders)
+        };
 
-    async fn update(&mut self, game: Arc<RwLock<Game>>) {
-        println!("Seek state");
-        // CHECK END OF TIMER
-        // CHECK IF SEEKER HAS FOUND HIDER
-        //
+        for capture in captures {
+            tracing::info!("{} was caught by {}", capture.hider, capture.seeker);
+            announce(&game, json!({
+                "op": "capture",
+                "hider": capture.hider,
+                "seeker": capture.seeker,
+                "latitude": capture.location.latitude,
+                "longitude": capture.location.longitude,
+                "station": capture.station,
+                "elapsed": capture.elapsed.as_secs(),
+            }))
+            .await;
+            self.captures.push(capture);
+        }
+
+        // the round is over once time runs out or every hider has been found
+        let all_caught = hiders > 0 && self.captures.len() >= hiders;
+        if self.timer.is_finished() || all_caught {
+            if let Err(e) = game.read().await.tx.try_send(Event::RoundEnd) {
+                tracing::error!("Failed to end the seek phase. {}", e);
+                return;
+            }
+            self.record_result(&game, all_caught).await;
+            self.done = true;
+            return;
+        }
+
+        self.send_zone(&game).await;
+
+        let interval = game.read().await.settings.countdown_interval();
+        if self.last_countdown.elapsed() >= interval {
+            self.last_countdown = Instant::now();
+            announce(&game, json!({
+                "op": "countdown",
+                "state": "Seek",
+                "remaining": self.remaining().as_secs(),
+            }))
+            .await;
+        }
     }
 
     fn new() -> Self {
-        SeekState
+        SeekState {
+            timer: Timer::new(Duration::ZERO),
+            captures: Vec::new(),
+            zone_sent: None,
+            last_countdown: Instant::now(),
+            done: false,
+        }
     }
 }
 
 #[derive(Clone)]
-pub struct RoundEndState;
+pub struct RoundEndState {
+    pub timer: Timer, // intermission before the next round
+    done: bool,
+}
+
 #[async_trait::async_trait]
 impl State for RoundEndState {
     async fn init(&mut self, game: Arc<RwLock<Game>>) {
-        let state = serde_json::to_string(&json!({
+        let (result, standings, intermission) = {
+            let game = game.read().await;
+            (
+                game.scores.last().cloned(),
+                game.scores.standings(game.teams.keys()),
+                game.settings.intermission(),
+            )
+        };
+        self.timer = Timer::new(intermission);
+
+        announce(&game, json!({
             "op": "state",
             "state": "RoundEnd",
+            "result": result,
+            "standings": standings,
+            "duration": intermission.as_secs(),
         }))
-        .unwrap();
+        .await;
+        tracing::info!("Init RoundEnd state");
+    }
+
+    async fn update(&mut self, game: Arc<RwLock<Game>>) {
+        if self.done || !self.timer.is_finished() {
+            return;
+        }
 
-        if let Err(e) = game
-            .write()
-            .await
-            .broadcast(Message::Text(state))
-            .await {
-            tracing::error!("Failed to update the state. {}", e);
+        // keep going until every team has had its turn to hide
+        let game = game.read().await;
+        let next = if game.has_next_round() {
+            Event::Hide
+        } else {
+            Event::GameOver
+        };
+        if let Err(e) = game.tx.try_send(next) {
+            tracing::error!("Failed to end the round. {}", e);
+            return;
         }
-        println!("Init RoundEnd state");
+        self.done = true;
+    }
+
+    fn new() -> Self {
+        RoundEndState {
+            timer: Timer::new(Duration::ZERO),
+            done: false,
+        }
+    }
+}
+
+// The final standings of the session, winner first. Its own op, clients only know the phases a round goes through.
+fn game_over(game: &Game) -> serde_json::Value {
+    let standings = game.scores.standings(game.teams.keys());
+    json!({
+        "op": "gameover",
+        "winner": standings.first(),
+        "standings": standings,
+    })
+}
+
+#[derive(Clone)]
+pub struct GameOverState {
+    pub timer: Timer, // how long the final standings are shown before going back to the lobby
+    done: bool,
+}
+
+#[async_trait::async_trait]
+impl State for GameOverState {
+    async fn init(&mut self, game: Arc<RwLock<Game>>) {
+        let (msg, intermission) = {
+            let game = game.read().await;
+            (game_over(&game), game.settings.intermission())
+        };
+        self.timer = Timer::new(intermission);
+        announce(&game, msg).await;
+        tracing::info!("Init GameOver state");
     }
 
     async fn update(&mut self, game: Arc<RwLock<Game>>) {
-        println!("RoundEnd state");
+        if self.done || !self.timer.is_finished() {
+            return;
+        }
+
+        if let Err(e) = game.read().await.tx.try_send(Event::Lobby) {
+            tracing::error!("Failed to return to the lobby. {}", e);
+            return;
+        }
+        self.done = true;
     }
 
     fn new() -> Self {
-        RoundEndState
+        GameOverState {
+            timer: Timer::new(Duration::ZERO),
+            done: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{scores::RoundResult, teams::Team};
+
+    async fn game(hider: &str, seekers: &[&str]) -> Arc<RwLock<Game>> {
+        let mut teams = vec![hider];
+        teams.extend(seekers);
+        let (mut game, _) = Game::offline(&teams).await;
+        for name in teams {
+            game.new_team(Team::new(name.to_string())).await.unwrap();
+        }
+        game.round = 1;
+        game.assign_roles(hider);
+        Arc::new(RwLock::new(game))
+    }
+
+    fn capture(seeker: &str, station: &str, seconds: u64) -> Capture {
+        Capture {
+            hider: "alpha".to_string(),
+            seeker: seeker.to_string(),
+            location: Location::new(22.3, 114.2),
+            station: Some(Code::from_str(station).unwrap()),
+            elapsed: Duration::from_secs(seconds),
+        }
+    }
+
+    #[tokio::test]
+    async fn caught_hiders_score_until_the_last_capture() {
+        let game = game("alpha", &["bravo", "charlie"]).await;
+        let mut seek = SeekState::new();
+        seek.timer = Timer::new(Duration::from_secs(3600));
+        seek.captures = vec![capture("bravo", "MOK", 100), capture("charlie", "YMT", 250)];
+        seek.record_result(&game, true).await;
+
+        let game = game.read().await;
+        let result = game.scores.last().unwrap();
+        assert_eq!(result.round, 1);
+        assert_eq!(result.hider, "alpha");
+        assert_eq!(result.survived, 250);
+        assert!(result.caught);
+        assert_eq!(result.caught_by.as_deref(), Some("charlie"));
+        assert_eq!(result.capture_station, Code::from_str("YMT").ok());
+    }
+
+    #[tokio::test]
+    async fn hiders_that_got_away_score_the_whole_seek_phase() {
+        let game = game("alpha", &["bravo"]).await;
+        let mut seek = SeekState::new();
+        seek.timer = Timer::new(Duration::from_secs(3600));
+        seek.captures = vec![capture("bravo", "MOK", 100)];
+        seek.record_result(&game, false).await;
+
+        let game = game.read().await;
+        let result = game.scores.last().unwrap();
+        // however long the seek phase has run for, not when one of them was caught
+        assert_eq!(result.survived, seek.timer.elapsed().as_secs());
+        assert_ne!(result.survived, 100);
+        assert!(!result.caught);
+        assert_eq!(result.caught_by, None);
+        assert_eq!(result.capture_station, None);
+    }
+
+    #[tokio::test]
+    async fn the_session_ends_once_every_team_has_hidden_once() {
+        let (mut game, mut rx) = Game::offline(&[]).await;
+        for name in ["charlie", "alpha", "bravo"] {
+            game.new_team(Team::new(name.to_string())).await.unwrap();
+        }
+        game.new_session();
+        let game = Arc::new(RwLock::new(game));
+
+        let mut hiders = vec![];
+        loop {
+            let mut hide = HideState::new();
+            hide.init(game.clone()).await;
+            hiders.push(game.read().await.hider_team().unwrap().name.clone());
+
+            // the intermission is over
+            let mut round_end = RoundEndState::new();
+            round_end.update(game.clone()).await;
+            match rx.try_recv() {
+                Ok(Event::Hide) if hiders.len() < 10 => continue,
+                Ok(Event::GameOver) => break,
+                event => panic!("unexpected {:?} after {:?}", event, hiders),
+            }
+        }
+        assert_eq!(hiders, ["alpha", "bravo", "charlie"]);
+        assert_eq!(game.read().await.round, 3);
+    }
+
+    #[tokio::test]
+    async fn the_final_standings_put_the_winner_first() {
+        let (mut game, _) = Game::offline(&[]).await;
+        for name in ["alpha", "bravo", "charlie"] {
+            game.new_team(Team::new(name.to_string())).await.unwrap();
+        }
+        for (round, hider, survived) in [(1, "alpha", 200), (2, "bravo", 900), (3, "charlie", 450)] {
+            game.scores.record(RoundResult {
+                round,
+                hider: hider.to_string(),
+                survived,
+                caught: true,
+                caught_by: None,
+                capture_station: None,
+            });
+        }
+
+        let msg = game_over(&game);
+        assert_eq!(msg["op"], "gameover");
+        assert_eq!(msg["winner"], json!({"team": "bravo", "score": 900}));
+        assert_eq!(
+            msg["standings"],
+            json!([
+                {"team": "bravo", "score": 900},
+                {"team": "charlie", "score": 450},
+                {"team": "alpha", "score": 200},
+            ])
+        );
+    }
+
+    fn codes(zone: &Zone) -> Vec<Code> {
+        zone.stations.iter().map(|station| station.code.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn the_zone_grows_a_minute_at_a_time_from_the_last_reveal() {
+        let (mut game, _) = Game::offline(&[]).await;
+        let mut seek = SeekState::new();
+        let minutes = |minutes: u64, seconds: u64| Duration::from_secs(minutes * 60 + seconds);
+        assert!(seek.next_zone(&game, minutes(5, 0)).is_none());
+
+        let mok = Code::from_str("MOK").unwrap();
+        game.sighting = Some(Sighting {
+            stations: vec![mok.clone()],
+            elapsed: minutes(10, 0),
+        });
+        let zone = seek.next_zone(&game, minutes(10, 30)).unwrap();
+        assert_eq!(zone.from, vec![mok.clone()]);
+        assert_eq!(zone.minutes, 0);
+        assert_eq!(codes(&zone), vec![mok.clone()]);
+        // nothing new until the next minute
+        assert!(seek.next_zone(&game, minutes(10, 59)).is_none());
+        assert_eq!(seek.next_zone(&game, minutes(11, 0)).unwrap().minutes, 1);
+
+        let ten = seek.next_zone(&game, minutes(20, 0)).unwrap();
+        let thirty = seek.next_zone(&game, minutes(40, 0)).unwrap();
+        assert_eq!((ten.minutes, thirty.minutes), (10, 30));
+        assert!(codes(&ten).iter().all(|code| codes(&thirty).contains(code)));
+        assert!(thirty.stations.len() > ten.stations.len());
+
+        // revealing them again starts over from where they are now
+        let tsw = Code::from_str("TSW").unwrap();
+        game.sighting = Some(Sighting {
+            stations: vec![tsw.clone()],
+            elapsed: minutes(40, 0),
+        });
+        let zone = seek.next_zone(&game, minutes(40, 20)).unwrap();
+        assert_eq!(zone.from, vec![tsw.clone()]);
+        assert_eq!(codes(&zone), vec![tsw]);
     }
 }
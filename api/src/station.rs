@@ -1,32 +1,71 @@
 use axum::response::IntoResponse;
 use axum::{Extension, Json};
-use serde::Serializer;
+use serde::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::ops::Add;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::game::Game;
+use crate::location::{haversine, Location};
 
-// MTR Station
+// Station of the network, interchanges are a single station with a platform for every line
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Station {
     pub code: Code,
     pub name: String,
     pub latitude: f64,
     pub longitude: f64,
+    pub lines: Vec<Platform>,
+}
+
+// Where a line stops at a station, the platforms of an interchange can be a walk apart.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Platform {
     pub line_code: Code,
+    pub latitude: f64,
+    pub longitude: f64,
 }
 
-// Code for an MTR Station with 3 characters like LOW for lowu
-#[derive(Debug, Deserialize, Copy, Clone, Eq)]
-pub struct Code(pub char, pub char, pub char);
+// Code for a station or line, any length, like LOW for lowu on the MTR or NS24 on the Singapore MRT
+#[derive(Debug, Clone, Eq, PartialOrd, Ord)]
+pub struct Code(pub String);
+
+// Line code of the walking links between stations
+pub const WALK: &str = "WLK";
+
+// Station code 1, Station code 2, Distance in minutes, Line code
+#[derive(Debug, Deserialize, Serialize, Clone, Eq)]
+pub struct Connection(pub Code, pub Code, pub usize, pub Code);
+
+impl Station {
+    // Codes of every line serving the station.
+    pub fn line_codes(&self) -> Vec<Code> {
+        self.lines.iter().map(|platform| platform.line_code.clone()).collect()
+    }
+
+    pub fn is_on_line(&self, line_code: &Code) -> bool {
+        self.lines.iter().any(|platform| platform.line_code == *line_code)
+    }
+
+    pub fn is_interchange(&self) -> bool {
+        self.lines.len() > 1
+    }
+
+    // A line can be listed more than once, e.g. at the start of a branch.
+    pub fn add_platform(&mut self, platform: Platform) {
+        if !self.is_on_line(&platform.line_code) {
+            self.lines.push(platform);
+        }
+    }
 
-// Station code 1, Station code 2, Distance in minutes
-#[derive(Debug, Deserialize, Serialize, Copy, Clone, Eq)]
-pub struct Connection(pub Code, pub Code, pub usize);
+    // Distance from the station to a location in metres.
+    pub fn distance(&self, location: &Location) -> f64 {
+        haversine(self.latitude, self.longitude, location.latitude, location.longitude)
+    }
+}
 
 // Compare stations against other stations by their code
 impl PartialEq for Station {
@@ -37,12 +76,12 @@ impl PartialEq for Station {
 
 // Compare a station against a code
 impl PartialEq<Code> for Station {
-    fn eq(&self, &other: &Code) -> bool {
-        self.code == other
+    fn eq(&self, other: &Code) -> bool {
+        self.code == *other
     }
 }
 
-// Compare a station against a string (first 3 chars)
+// Compare a station against a string
 impl PartialEq<&str> for Station {
     fn eq(&self, other: &&str) -> bool {
         self.code == *other
@@ -52,7 +91,7 @@ impl PartialEq<&str> for Station {
 // Trait so that the Station type can be hashed according to its Code
 impl Hash for Station {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
-        hasher.write(self.code.to_string().as_bytes());
+        self.code.hash(hasher);
     }
 }
 
@@ -61,74 +100,85 @@ impl Serialize for Code {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        serializer.serialize_str(&self.0)
+    }
+}
+
+// codes are written as plain strings like "NAC" or "NS24" in the data files
+impl<'de> Deserialize<'de> for Code {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let code = String::deserialize(deserializer)?;
+        Code::from_str(&code).map_err(|_| serde::de::Error::custom(format!("invalid code {}", code)))
     }
 }
 
 impl FromStr for Code {
     type Err = ();
 
+    // anything without spaces or commas, which would break the csv data
     fn from_str(code: &str) -> Result<Self, Self::Err> {
-        let code = code.chars().collect::<Vec<char>>();
-        if code.len() != 3 {
+        if code.is_empty() || code.contains(|c: char| c.is_whitespace() || c == ',') {
             Err(())
         } else {
-            Ok(Self(code[0], code[1], code[2]))
+            Ok(Self(code.to_string()))
         }
     }
 }
 
-// simple way to add two codes together
-impl Add for Code {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        let c1 = self.0 as u32 + other.0 as u32;
-        let c2 = self.1 as u32 + other.1 as u32;
-        let c3 = self.2 as u32 + other.2 as u32;
-
-        Self(
-            std::char::from_u32(c1 % 0x110000).unwrap_or('\0'),
-            std::char::from_u32(c2 % 0x110000).unwrap_or('\0'),
-            std::char::from_u32(c3 % 0x110000).unwrap_or('\0'),
-        )
+impl Code {
+    // The line walking links between stations are on.
+    pub fn walk() -> Self {
+        Code(WALK.to_string())
     }
 }
 
 // convert a code to a string (datatype)
-impl ToString for Code {
-    fn to_string(&self) -> String {
-        format!("{}{}{}", self.0, self.1, self.2)
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
 // trait to compare codes
 impl PartialEq for Code {
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0 && self.1 == other.1 && self.2 == other.2
+        self.0 == other.0
     }
 }
 
-// trait to compare a code against a string (first 3 chars)
+// trait to compare a code against a string
 impl PartialEq<&str> for Code {
     fn eq(&self, other: &&str) -> bool {
-        self.0 == other.chars().nth(0).unwrap()
-            && self.1 == other.chars().nth(1).unwrap()
-            && self.2 == other.chars().nth(2).unwrap()
+        self.0 == *other
     }
 }
 
 // Trait to hash a Code
 impl Hash for Code {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
-        hasher.write(self.to_string().as_bytes());
+        self.0.hash(hasher);
+    }
+}
+
+impl Connection {
+    // The station at the other end of the connection.
+    pub fn other(&self, code: &Code) -> &Code {
+        if self.0 == *code {
+            &self.1
+        } else {
+            &self.0
+        }
     }
 }
 
-// trait to compare connections
+// trait to compare connections, the same two stations can be connected by more than one line
 impl PartialEq for Connection {
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0 && self.1 == other.1 || self.0 == other.1 && self.1 == other.0
+        (self.0 == other.0 && self.1 == other.1 || self.0 == other.1 && self.1 == other.0)
+            && self.3 == other.3
     }
 }
 
@@ -149,8 +199,11 @@ impl PartialEq<&str> for Connection {
 // hash connections by their codes, so we can have a hashset of connections.
 impl Hash for Connection {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
-        hasher.write((self.0 + self.1).to_string().as_bytes());
-        // add together the two codes and save them as bytes.
+        // hash the codes in order so both directions hash the same.
+        let (a, b) = if self.0 <= self.1 { (&self.0, &self.1) } else { (&self.1, &self.0) };
+        a.hash(hasher);
+        b.hash(hasher);
+        self.3.hash(hasher);
     }
 }
 
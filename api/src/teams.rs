@@ -174,6 +174,21 @@ pub async fn ready(
 }
 
 
+// admin only, choose which team hides in the next round
+pub async fn set_hider(
+    Extension(game): Extension<Arc<RwLock<Game>>>,
+    Path(team_name): Path<String>,
+    Extension(username): Extension<String>,
+) -> impl IntoResponse {
+    let mut game = game.write().await;
+    if !game.teams.contains_key(&team_name) {
+        return Json(json!({"error": "Team not found"})).into_response();
+    }
+    game.next_hider = Some(team_name.clone());
+    info!("admin {} chose {} to hide next", username, team_name);
+    Json(json!({"message": "Hider team chosen"})).into_response()
+}
+
 pub async fn update_team_name(
     Extension(game): Extension<Arc<RwLock<Game>>>,
     Path(team_name): Path<String>,
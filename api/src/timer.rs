@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+// A countdown for a timed phase of the game, which can be paused.
+#[derive(Debug, Clone)]
+pub struct Timer {
+    started: Instant,
+    started_at: DateTime<Utc>, // wall clock time the timer was started, for clients
+    duration: Duration,
+    paused_at: Option<Instant>,
+}
+
+impl Timer {
+    pub fn new(duration: Duration) -> Self {
+        Timer {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            duration,
+            paused_at: None,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        // time stands still while paused
+        let now = self.paused_at.unwrap_or_else(Instant::now);
+        now.saturating_duration_since(self.started)
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.elapsed())
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    // When the timer is expected to run out, if it keeps running from now on and that is a date at all.
+    pub fn ends_at(&self) -> Option<DateTime<Utc>> {
+        let remaining = chrono::Duration::from_std(self.remaining()).ok()?;
+        Utc::now().checked_add_signed(remaining)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed() >= self.duration
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(Instant::now());
+        }
+    }
+
+    // Lengthen (positive) or shorten (negative) the countdown by some seconds.
+    pub fn adjust(&mut self, seconds: i64) {
+        let change = Duration::from_secs(seconds.unsigned_abs());
+        if seconds >= 0 {
+            self.duration = self.duration.saturating_add(change);
+        } else {
+            self.duration = self.duration.saturating_sub(change);
+        }
+    }
+
+    // Run out the clock right away, a paused timer stays paused.
+    pub fn finish(&mut self) {
+        self.duration = self.elapsed();
+    }
+
+    pub fn resume(&mut self) {
+        // push the start forward by however long we were paused for
+        if let Some(paused_at) = self.paused_at.take() {
+            self.started += paused_at.elapsed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use super::*;
+
+    #[test]
+    fn counts_down_from_the_duration() {
+        let timer = Timer::new(Duration::from_secs(60));
+        assert!(timer.remaining() <= Duration::from_secs(60));
+        assert!(timer.remaining() > Duration::from_secs(59));
+        assert!(!timer.is_finished());
+        assert!(!timer.is_paused());
+
+        assert!(Timer::new(Duration::ZERO).is_finished());
+    }
+
+    #[test]
+    fn time_stands_still_while_paused() {
+        let mut timer = Timer::new(Duration::from_secs(60));
+        sleep(Duration::from_millis(10));
+        timer.pause();
+        let elapsed = timer.elapsed();
+        assert!(elapsed >= Duration::from_millis(10));
+        sleep(Duration::from_millis(30));
+        assert_eq!(timer.elapsed(), elapsed);
+
+        // pausing again doesn't move when the pause started
+        timer.pause();
+        assert_eq!(timer.elapsed(), elapsed);
+
+        timer.resume();
+        assert!(!timer.is_paused());
+        assert!(timer.elapsed() < elapsed + Duration::from_millis(30));
+        // and resuming a running timer does nothing
+        timer.resume();
+        assert!(timer.elapsed() < elapsed + Duration::from_millis(30));
+    }
+
+    #[test]
+    fn adjusting_lengthens_or_shortens_the_countdown() {
+        let mut timer = Timer::new(Duration::from_secs(60));
+        timer.adjust(30);
+        assert!(timer.remaining() > Duration::from_secs(89));
+        timer.adjust(-45);
+        assert!(timer.remaining() <= Duration::from_secs(45));
+        assert!(timer.remaining() > Duration::from_secs(44));
+
+        // past zero just runs it out
+        timer.adjust(-120);
+        assert_eq!(timer.remaining(), Duration::ZERO);
+        assert!(timer.is_finished());
+    }
+
+    #[test]
+    fn huge_adjustments_saturate() {
+        let mut timer = Timer::new(Duration::from_secs(60));
+        timer.adjust(i64::MAX);
+        timer.adjust(i64::MAX);
+        assert_eq!(timer.duration, Duration::MAX);
+        assert!(!timer.is_finished());
+        // too far away to be a date, but asking doesn't panic
+        assert_eq!(timer.ends_at(), None);
+    }
+
+    #[test]
+    fn finishing_keeps_the_pause() {
+        let mut timer = Timer::new(Duration::from_secs(60));
+        timer.pause();
+        timer.finish();
+        assert!(timer.is_finished());
+        assert!(timer.is_paused());
+        assert_eq!(timer.remaining(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::{
+    graph::Graph,
+    station::{Code, Connection},
+};
+
+// A problem with the network data, either found while reading it or by `Graph::validate`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum Issue {
+    OutOfBounds { code: Code, line: Code, latitude: f64, longitude: f64 },
+    ConflictingNames { code: Code, names: (String, String) },
+    MissingTime { from: Code, to: Code, line: Code },
+    ZeroTime { from: Code, to: Code, line: Code },
+    Loop { code: Code, line: Code },
+    UnknownStation { code: Code, line: Code },
+    Asymmetric { from: Code, to: Code, line: Code, minutes: (usize, usize) },
+    Disconnected { stations: Vec<Code> },
+    NoCommonLine { from: Code, to: Code },
+    SameLocation { stations: (Code, Code) },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::OutOfBounds { code, line, latitude, longitude } => {
+                write!(f, "{} on {} is outside the map at {}, {}", code, line, latitude, longitude)
+            }
+            Issue::ConflictingNames { code, names } => {
+                write!(f, "{} is called both {} and {}", code, names.0, names.1)
+            }
+            Issue::MissingTime { from, to, line } => {
+                write!(f, "{} to {} on {} has no travel time", from, to, line)
+            }
+            Issue::ZeroTime { from, to, line } => {
+                write!(f, "{} to {} on {} takes no time", from, to, line)
+            }
+            Issue::Loop { code, line } => write!(f, "{} connects to itself on {}", code, line),
+            Issue::UnknownStation { code, line } => {
+                write!(f, "a connection on {} leads to {} which is not a station", line, code)
+            }
+            Issue::Asymmetric { from, to, line, minutes } => write!(
+                f,
+                "{} to {} on {} takes {} minutes one way and {} the other",
+                from, to, line, minutes.0, minutes.1
+            ),
+            Issue::Disconnected { stations } => {
+                let codes: Vec<String> = stations.iter().map(|code| code.to_string()).collect();
+                write!(f, "{} cannot be reached from the rest of the network", codes.join(", "))
+            }
+            Issue::SameLocation { stations } => {
+                write!(f, "{} and {} are at exactly the same place", stations.0, stations.1)
+            }
+            Issue::NoCommonLine { from, to } => {
+                write!(f, "{} to {} is left out as the stations have no line in common", from, to)
+            }
+        }
+    }
+}
+
+impl Graph {
+    // Everything that looks wrong with the network, an empty list means the data is fine.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = self.load_issues.clone();
+
+        let mut stations: Vec<_> = self.stations.values().collect();
+        stations.sort_by(|a, b| a.code.cmp(&b.code));
+        let mut locations: HashMap<(u64, u64), &Code> = HashMap::new();
+        for station in stations {
+            for platform in station.lines.iter() {
+                if !self.bounds.contains(platform.latitude, platform.longitude) {
+                    issues.push(Issue::OutOfBounds {
+                        code: station.code.clone(),
+                        line: platform.line_code.clone(),
+                        latitude: platform.latitude,
+                        longitude: platform.longitude,
+                    });
+                }
+            }
+            // most likely copied from another station by mistake, which would then get a walk to it for nothing
+            let location = (station.latitude.to_bits(), station.longitude.to_bits());
+            if let Some(other) = locations.insert(location, &station.code) {
+                issues.push(Issue::SameLocation { stations: (other.clone(), station.code.clone()) });
+            }
+        }
+
+        let mut connections: Vec<_> = self.connections.iter().collect();
+        connections.sort_by(|a, b| (&a.0, &a.1, &a.3).cmp(&(&b.0, &b.1, &b.3)));
+        for Connection(from, to, minutes, line) in connections {
+            for code in [from, to] {
+                if !self.stations.contains_key(code) {
+                    issues.push(Issue::UnknownStation { code: code.clone(), line: line.clone() });
+                }
+            }
+            if from == to {
+                issues.push(Issue::Loop { code: from.clone(), line: line.clone() });
+            } else if *minutes == 0 && !self.load_issues.iter().any(|issue| {
+                // already reported as missing rather than zero
+                matches!(issue, Issue::MissingTime { from: a, to: b, line: l }
+                    if l == line && (a == from && b == to || a == to && b == from))
+            }) {
+                issues.push(Issue::ZeroTime { from: from.clone(), to: to.clone(), line: line.clone() });
+            }
+        }
+
+        // everything outside the largest component is unreachable from most of the network
+        let mut components = self.components();
+        components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+        for stations in components.into_iter().skip(1) {
+            issues.push(Issue::Disconnected { stations });
+        }
+
+        issues
+    }
+
+    // Groups of stations that are connected to each other, ignoring travel times.
+    fn components(&self) -> Vec<Vec<Code>> {
+        let mut codes: Vec<&Code> = self.stations.keys().collect();
+        codes.sort();
+
+        let mut seen = HashSet::new();
+        let mut components = vec![];
+        for start in codes {
+            if !seen.insert(start.clone()) {
+                continue;
+            }
+            let mut component = vec![start.clone()];
+            let mut queue = VecDeque::from([start.clone()]);
+            while let Some(code) = queue.pop_front() {
+                // only the links in the data, the generated walks would hide gaps in it
+                for connection in self.connections.iter().filter(|connection| **connection == code) {
+                    let next = connection.other(&code);
+                    if self.stations.contains_key(next) && seen.insert(next.clone()) {
+                        component.push(next.clone());
+                        queue.push_back(next.clone());
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn code(code: &str) -> Code {
+        Code::from_str(code).unwrap()
+    }
+
+    // rows as they were in the built in csv before it was fixed
+    const ORIGINAL_ROWS: &str = "KOT,Kowloon Tong,22.3369989,114.1758397,-,EAL
+MKK,Mong Kok East,22.3217126,22.3217126,4,EAL
+HUH,Hung Hom,22.3027415,114.182136,5,EAL
+,,,,,
+YMT,Yau Ma Tei,22.3130692,114.1707484,-,KTL
+HOM,Ho Man Tin,22.3093727,22.3093727,4,KTL
+WHA,Whampoa,22.304901,114.1896461,4,KTL
+,,,,,
+HUH,Hung Hom,22.3027415,114.182136,-,TML
+HOM,Ho Man Tin,22.3093727,22.3093727,4,TML
+,,,,,
+MOS,Ma On Shan,22.4249134,114.2317637,-,TML
+WKS,Wu Kai Sha,22.4292202,114.243854,,TML
+,,,,,
+HAH,Hang Hau,22.3155885,114.2644721,-,TKL
+TKO,Tseung Kwan O,22.3074422,114.2599465,4,TKL
+TKO,Lohas Park,22.2956555,114.2687716,7,TKL
+TIK,Tiu Keng Leng,22.3040231,114.252426,3,TKL";
+
+    fn issues(csv: &str) -> Vec<Issue> {
+        Graph::parse_csv(csv).unwrap().validate()
+    }
+
+    #[test]
+    fn latitude_in_the_longitude_column() {
+        let out_of_bounds: Vec<(Code, Code)> = issues(ORIGINAL_ROWS)
+            .into_iter()
+            .filter_map(|issue| match issue {
+                Issue::OutOfBounds { code, line, .. } => Some((code, line)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            out_of_bounds,
+            vec![
+                (code("HOM"), code("KTL")),
+                (code("HOM"), code("TML")),
+                (code("MKK"), code("EAL")),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_minutes() {
+        let issues = issues(ORIGINAL_ROWS);
+        assert!(issues.iter().any(|issue| matches!(issue,
+            Issue::MissingTime { from, to, line } if *from == code("MOS") && *to == code("WKS") && *line == code("TML"))));
+        // reported once, not again as taking no time
+        assert!(!issues.iter().any(|issue| matches!(issue, Issue::ZeroTime { .. })));
+    }
+
+    #[test]
+    fn lohas_park_coded_as_tseung_kwan_o() {
+        let issues = issues(ORIGINAL_ROWS);
+        assert!(issues.iter().any(|issue| matches!(issue,
+            Issue::ConflictingNames { code: c, names } if *c == code("TKO") && names.0 == "Tseung Kwan O" && names.1 == "Lohas Park")));
+        assert!(issues.iter().any(|issue| matches!(issue,
+            Issue::Loop { code: c, line } if *c == code("TKO") && *line == code("TKL"))));
+    }
+
+    #[test]
+    fn disconnected_stretches() {
+        let disconnected: Vec<Vec<Code>> = issues(ORIGINAL_ROWS)
+            .into_iter()
+            .filter_map(|issue| match issue {
+                Issue::Disconnected { stations } => Some(stations),
+                _ => None,
+            })
+            .collect();
+        // everything joined up through Hung Hom and Ho Man Tin is the main network
+        assert_eq!(disconnected.len(), 2);
+        assert!(disconnected.contains(&vec![code("HAH"), code("TIK"), code("TKO")]));
+        assert!(disconnected.contains(&vec![code("MOS"), code("WKS")]));
+    }
+
+    #[test]
+    fn different_minutes_each_way() {
+        let issues = issues(
+            "MOS,Ma On Shan,22.4249134,114.2317637,-,TML
+WKS,Wu Kai Sha,22.4292202,114.243854,3,TML
+,,,,,
+WKS,Wu Kai Sha,22.4292202,114.243854,-,TML
+MOS,Ma On Shan,22.4249134,114.2317637,4,TML",
+        );
+        assert!(issues.iter().any(|issue| matches!(issue,
+            Issue::Asymmetric { line, minutes, .. } if *line == code("TML") && *minutes == (3, 4))));
+    }
+
+    #[test]
+    fn zero_times_and_unknown_stations() {
+        // connections as they were in the ron data, Tsim Sha Tsui is left out of the stations
+        let graph = Graph::parse_network(
+            r#"(
+                projection: (latitude: 22.32, longitude: 114.17, x: 0, y: 0, x_per_degree: 1.0, y_per_degree: 1.0),
+                bounds: (latitude: (22.1, 22.6), longitude: (113.8, 114.5)),
+                stations: [
+                    (code: "HOK", name: "Hong Kong", latitude: 22.2851469, longitude: 114.1584546),
+                    (code: "MOS", name: "Ma On Shan", latitude: 22.4249134, longitude: 114.2317637),
+                    (code: "WKS", name: "Wu Kai Sha", latitude: 22.4292202, longitude: 114.243854),
+                ],
+                connections: [
+                    ("HOK", "TST", 0, "WLK"),
+                    ("MOS", "WKS", 0, "TML"),
+                ],
+            )"#,
+        )
+        .unwrap();
+        let issues = graph.validate();
+        assert!(issues.iter().any(|issue| matches!(issue,
+            Issue::UnknownStation { code: c, line } if *c == code("TST") && *line == code("WLK"))));
+        assert!(issues.iter().any(|issue| matches!(issue,
+            Issue::ZeroTime { from, to, .. } if *from == code("MOS") && *to == code("WKS"))));
+    }
+
+    #[test]
+    fn tsuen_wan_at_tsuen_wan_west() {
+        let issues = issues(
+            "MEF,Mei Foo,22.3382,114.1379,-,TML
+TWW,Tsuen Wan West,22.3683642,114.1099384,7,TML
+,,,,,
+TSW,Tsuen Wan,22.3683642,114.1099384,-,TWL
+TWH,Tai Wo Hau,22.3708,114.125,3,TWL",
+        );
+        assert!(issues.iter().any(|issue| matches!(issue,
+            Issue::SameLocation { stations } if stations.0 == code("TSW") && stations.1 == code("TWW"))));
+    }
+
+    #[test]
+    fn built_in_data_is_clean() {
+        assert!(Graph::from_csv().validate().is_empty());
+    }
+}
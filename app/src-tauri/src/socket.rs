@@ -112,6 +112,7 @@ async fn handle_json_message(text: String, app: AppHandle) {
                     "notif" => handle_notif(json, app).await,
                     "state" => handle_state(json, app).await.expect("STATE ERROR"),
                     "chat" => handle_chat(json, app).await.expect("CHAT ERROR"),
+                    "gameover" => handle_gameover(json, app).await,
                     "location" => handle_location(json).await,
                     _ => println!("Unknown operation: {}", op),
                 }
@@ -222,6 +223,19 @@ async fn handle_state(json: Value, app: AppHandle) -> Result<(), String> {
     }
 }
 
+async fn handle_gameover(json: Value, app: AppHandle) {
+    let body = match json.get("winner").and_then(|winner| winner.get("team")).and_then(Value::as_str) {
+        Some(team) => format!("{} won!", team),
+        None => "Nobody won".to_string(),
+    };
+    app.notification()
+        .builder()
+        .title("Game Over")
+        .body(body)
+        .show()
+        .unwrap();
+}
+
 async fn handle_location(json: Value) {
 
 }